@@ -0,0 +1,94 @@
+/*
+ * The C interface to chemtool, exported by the cdylib built from the crate (libchemtool.so).
+ *
+ * Every function which can fail returns 0 on success, and otherwise the kind of the error:
+ * 1 for invalid input, 2 for a misuse of the interface and 3 for a problem with the database.
+ * The error itself is then stored in *err, unless err is NULL, and must be freed with
+ * ct_error_free.
+ *
+ * Everything returned through a pointer is owned by the caller, and must be freed by the
+ * matching ct_*_free function. Strings returned as const char * are borrowed from the object
+ * they were taken from, and live as long as it does. All strings are UTF-8, and all positions
+ * in the input are byte offsets. A bug which would crash the library is reported as invalid
+ * input instead, so it never takes the calling program down with it.
+ */
+
+#ifndef CHEMTOOL_H
+#define CHEMTOOL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct ct_molecule ct_molecule;
+typedef struct ct_database ct_database;
+typedef struct ct_error ct_error;
+
+/* The status returned by the functions which can fail */
+enum {
+    CT_OK = 0,
+    CT_INPUT_ERROR = 1,
+    CT_USAGE_ERROR = 2,
+    CT_DATABASE_ERROR = 3
+};
+
+/* Parses a formula holding a single molecule, e.g. "H2O", and groups its elements */
+int ct_parse_molecule(const char *formula, ct_molecule **out, ct_error **err);
+/* Frees a molecule returned by ct_parse_molecule */
+void ct_molecule_free(ct_molecule *molecule);
+/* The number of elements in a molecule, where a charge counts as an element */
+size_t ct_molecule_len(const ct_molecule *molecule);
+/* The symbol of the element at the index, "+" or "-" for a charge, or NULL if there is none */
+const char *ct_molecule_symbol(const ct_molecule *molecule, size_t index);
+/* The count of the element at the index, or 0 if there is none */
+uint32_t ct_molecule_count(const ct_molecule *molecule, size_t index);
+/* Writes a molecule as a formula, which must be freed with ct_string_free */
+char *ct_molecule_formula(const ct_molecule *molecule);
+
+/* Opens the element database at the given path, e.g. "elemdb.csv" */
+int ct_database_open(const char *path, ct_database **out, ct_error **err);
+/* Closes a database opened by ct_database_open */
+void ct_database_free(ct_database *database);
+/* Finds the molar mass of a formula in g/mol, with CT_USAGE_ERROR if database is NULL */
+int ct_molar_mass(ct_database *database, const char *formula, double *mass, ct_error **err);
+
+/*
+ * Balances a reaction, e.g. "C3H8 + O2 -> CO2 + H2O", and writes the coefficient of each
+ * species to coefs, reactants first. The number of species is written to *count. If capacity
+ * is too small, nothing is written to coefs, CT_USAGE_ERROR is returned and *count is still
+ * set, so the call can be retried with a larger buffer.
+ */
+int ct_balance(const char *reaction, uint32_t *coefs, size_t capacity, size_t *count,
+               ct_error **err);
+
+/* The kind of an error, which is the same as the status returned with it */
+int ct_error_kind(const ct_error *err);
+/* The description of an error */
+const char *ct_error_message(const ct_error *err);
+/* The hint on how to fix an error, or NULL if there is none */
+const char *ct_error_help(const ct_error *err);
+/* Writes the span of the error in the input and returns 1, or returns 0 if it has none */
+int ct_error_span(const ct_error *err, size_t *pos, size_t *len);
+/* The number of labels of an error, i.e. the other parts of the input it points at */
+size_t ct_error_label_count(const ct_error *err);
+/* The text of the label at the index, writing its span, or NULL if there is none */
+const char *ct_error_label(const ct_error *err, size_t index, size_t *pos, size_t *len);
+/*
+ * Formats an error as the chemtool program prints it, pointing at the error in the input,
+ * which may be NULL. The message is coloured if color is non-zero, and must be freed with
+ * ct_string_free.
+ */
+char *ct_error_render(const ct_error *err, const char *input, int color);
+/* Frees an error returned by one of the functions */
+void ct_error_free(ct_error *err);
+/* Frees a string returned by ct_molecule_formula or ct_error_render */
+void ct_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
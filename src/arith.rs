@@ -0,0 +1,192 @@
+//! Arithmetic on the element counts of molecules, e.g. `C6H12O6 - C6H10O5 = H2O`.
+//!
+//! Every molecule made here is grouped and sorted like the output of `group_elems`, and its
+//! elements have no position in any input. The charge of a molecule is counted as a signed
+//! number, so e.g. adding `H^+` to `NH3` gives `H4N^+`, and adding `OH^-` to `H^+` gives `H2O`.
+
+use std::collections::BTreeMap;
+use crate::elem::{PerElem, Molecule, Formula, group_elems};
+use crate::error::{CTError, CTResult};
+use crate::error::CTErrorKind::InputError;
+
+/// The count of each periodic element in a molecule, along with its net charge
+struct Counts {
+    elems: BTreeMap<String, i64>,
+    charge: i64,
+}
+
+impl Counts {
+    fn new(molecule: &Molecule) -> Counts {
+        let mut counts = Counts { elems: BTreeMap::new(), charge: 0 };
+        for elem in molecule.iter() {
+            match elem.name.as_str() {
+                "+" => counts.charge += elem.coef as i64,
+                "-" => counts.charge -= elem.coef as i64,
+                name => *counts.elems.entry(name.to_string()).or_insert(0) += elem.coef as i64,
+            }
+        }
+        counts
+    }
+
+    /// Makes the molecule holding every element with a positive count
+    fn to_molecule(&self) -> Molecule {
+        let per_elem = |name: &str, count: i64| {
+            PerElem { name: name.to_string(), coef: count.min(u32::MAX as i64) as u32, pos: 0,
+                      len: 0 }
+        };
+        let mut molecule: Molecule = self.elems.iter()
+                                         .filter(|&(_, &count)| count > 0)
+                                         .map(|(name, &count)| per_elem(name, count))
+                                         .collect();
+        if self.charge > 0 {
+            molecule.push(per_elem("+", self.charge));
+        } else if self.charge < 0 {
+            molecule.push(per_elem("-", -self.charge));
+        }
+        group_elems(molecule)
+    }
+
+    /// Adds the counts of the other molecule, each multiplied by the factor
+    fn add(mut self, other: &Counts, factor: i64) -> Counts {
+        for (name, &count) in other.elems.iter() {
+            *self.elems.entry(name.clone()).or_insert(0) += count * factor;
+        }
+        self.charge += other.charge * factor;
+        self
+    }
+}
+
+/// Adds the elements and charges of two molecules, e.g. `NH3 + H^+ = H4N^+`
+pub fn add(a: &Molecule, b: &Molecule) -> Molecule {
+    Counts::new(a).add(&Counts::new(b), 1).to_molecule()
+}
+
+/// Multiplies the count of every element, and the charge, by the factor, e.g. `3 * H2O = H6O3`
+pub fn scale(molecule: &Molecule, factor: u32) -> Molecule {
+    Counts::new(molecule).add(&Counts::new(molecule), factor as i64 - 1).to_molecule()
+}
+
+/// Returns true if every element of `part` is found in `whole` at least as many times
+///
+/// The charges of the molecules are ignored, since a charge can always be taken away.
+pub fn contains(whole: &Molecule, part: &Molecule) -> bool {
+    let whole = Counts::new(whole);
+    Counts::new(part).elems.iter().all(|(name, &count)| {
+        whole.elems.get(name).copied().unwrap_or(0) >= count
+    })
+}
+
+/// Returns true if the molecules have the same elements and charge, no matter their order
+///
+/// E.g. `CH3CH2OH` and `C2H6O` are the same formula.
+pub fn same_formula(a: &Molecule, b: &Molecule) -> bool {
+    let (a, b) = (Counts::new(a), Counts::new(b));
+    a.charge == b.charge
+        && a.elems.iter().chain(b.elems.iter()).all(|(name, _)| {
+            a.elems.get(name).copied().unwrap_or(0) == b.elems.get(name).copied().unwrap_or(0)
+        })
+}
+
+/// Takes the elements of `part` away from `whole`, e.g. `C6H12O6 - C6H10O5 = H2O`
+///
+/// This errors if `whole` does not contain `part`, naming every element it has too few of.
+pub fn subtract(whole: &Molecule, part: &Molecule) -> CTResult<Molecule> {
+    let difference = Counts::new(whole).add(&Counts::new(part), -1);
+    let missing: Vec<&str> = difference.elems.iter()
+                                       .filter(|&(_, &count)| count < 0)
+                                       .map(|(name, _)| name.as_str())
+                                       .collect();
+    if missing.is_empty() {
+        Ok(difference.to_molecule())
+    } else {
+        let desc = format!("Can not take {} away from {}, which has too few: {}",
+                           Formula(part), Formula(whole), missing.join(", "));
+        Err(CTError::new(InputError, desc)
+            .with_help("use `diff` to find the elements each of them has in excess"))
+    }
+}
+
+/// Finds what was lost and what was gained going from `before` to `after`
+///
+/// The result is `(lost, gained)`, such that `before + gained = after + lost`, e.g. going from
+/// `C6H12O6` to `C6H10O5` loses `H2O` and gains nothing. A change of charge goes with the lost
+/// elements, unless only elements were gained, e.g. going from `NH3` to `NH4^+` gains `H^+`.
+pub fn diff(before: &Molecule, after: &Molecule) -> (Molecule, Molecule) {
+    let difference = Counts::new(before).add(&Counts::new(after), -1);
+    let mut lost = Counts { elems: BTreeMap::new(), charge: 0 };
+    let mut gained = Counts { elems: BTreeMap::new(), charge: 0 };
+    for (name, &count) in difference.elems.iter() {
+        if count > 0 {
+            lost.elems.insert(name.clone(), count);
+        } else if count < 0 {
+            gained.elems.insert(name.clone(), -count);
+        }
+    }
+    if lost.elems.is_empty() && !gained.elems.is_empty() {
+        gained.charge = -difference.charge;
+    } else {
+        lost.charge = difference.charge;
+    }
+    (lost.to_molecule(), gained.to_molecule())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse_single_molecule;
+
+    fn molecule(formula: &str) -> Molecule {
+        parse_single_molecule(formula).unwrap()
+    }
+
+    fn formula(molecule: &Molecule) -> String {
+        Formula(molecule).to_string()
+    }
+
+    #[test]
+    fn add_molecules() {
+        assert_eq!(formula(&add(&molecule("NH3"), &molecule("H^+"))), "H4N^+");
+        assert_eq!(formula(&add(&molecule("H^+"), &molecule("OH^-"))), "H2O");
+        assert!(add(&molecule("H2O"), &molecule("CO2")).iter().all(|e| e.pos == 0 && e.len == 0));
+    }
+
+    #[test]
+    fn scale_molecule() {
+        assert_eq!(formula(&scale(&molecule("SO4^2-"), 3)), "O12S3^6-");
+        assert_eq!(scale(&molecule("H2O"), 0), Vec::new());
+    }
+
+    #[test]
+    fn subtract_molecules() {
+        assert_eq!(subtract(&molecule("C6H12O6"), &molecule("C6H10O5")).map(|m| formula(&m)),
+                   Ok("H2O".to_string()));
+        let err = subtract(&molecule("H2O"), &molecule("H2O2C")).unwrap_err();
+        assert_eq!(err.desc, "Can not take CH2O2 away from H2O, which has too few: C, O");
+    }
+
+    #[test]
+    fn diff_molecules() {
+        let (lost, gained) = diff(&molecule("C6H12O6"), &molecule("C6H10O5"));
+        assert_eq!((formula(&lost), formula(&gained)), ("H2O".to_string(), "".to_string()));
+        let (lost, gained) = diff(&molecule("NH3"), &molecule("NH4^+"));
+        assert_eq!((formula(&lost), formula(&gained)), ("".to_string(), "H^+".to_string()));
+        let (lost, gained) = diff(&molecule("C2H6O"), &molecule("CH4"));
+        assert_eq!((formula(&lost), formula(&gained)), ("CH2O".to_string(), "".to_string()));
+        let (lost, gained) = diff(&molecule("NaCl"), &molecule("KCl"));
+        assert_eq!((formula(&lost), formula(&gained)), ("Na".to_string(), "K".to_string()));
+    }
+
+    #[test]
+    fn same() {
+        assert!(same_formula(&molecule("CH3CH2OH"), &molecule("C2H6O")));
+        assert!(!same_formula(&molecule("NH4"), &molecule("NH4^+")));
+        assert!(!same_formula(&molecule("H2O"), &molecule("H2O2")));
+    }
+
+    #[test]
+    fn contained() {
+        assert!(contains(&molecule("C6H12O6"), &molecule("H2O")));
+        assert!(contains(&molecule("SO4^2-"), &molecule("O2")));
+        assert!(!contains(&molecule("H2O"), &molecule("CO")));
+    }
+}
@@ -1,19 +1,27 @@
-use std::f64;
 use std::cmp::min;
-use std::num::Float;
 use std::ops::{Index, IndexMut, Range, RangeTo, RangeFrom, RangeFull};
-use elem;
-use elem::Molecule;
-use error::{CTResult, CTError};
-use error::CTErrorKind::InputError;
+use crate::elem;
+use crate::elem::{Molecule, Formula, Species, Reaction};
+use crate::json::Json;
+use crate::error::{CTResult, CTError, Label};
+use crate::error::CTErrorKind::InputError;
+
+/// The tolerance used when comparing the results of the elimination to zero or to integers
+const EPSILON: f64 = 1e-9;
+
+/// The largest multiplier tried when turning fractional coefficients into integers
+const MAX_MULTIPLIER: u32 = 1000;
+
+/// The header for the table made by `to_rows`
+pub static TABLE_HEADER: [&str; 3] = ["side", "coefficient", "formula"];
 
 macro_rules! impl_matrix_index {
     ($idx:ty, $out:ty) => {
         impl Index<$idx> for Matrix {
             type Output = $out;
 
-            fn index(&self, index: &$idx) -> &$out {
-                &self.buf[*index]
+            fn index(&self, index: $idx) -> &$out {
+                &self.buf[index]
             }
         }
     }
@@ -22,29 +30,62 @@ macro_rules! impl_matrix_index {
 macro_rules! impl_matrix_index_mut {
     ($idx:ty, $out:ty) => {
         impl IndexMut<$idx> for Matrix {
-            fn index_mut(&mut self, index: &$idx) -> &mut $out {
-                &mut self.buf[*index]
+            fn index_mut(&mut self, index: $idx) -> &mut $out {
+                &mut self.buf[index]
             }
         }
     }
 }
 
-/// Takes a parsed reaction and pretty prints it to the console
+/// Takes a balanced reaction and pretty prints it to the console
 ///
 /// The reaction is printed as follows:
 /// <coef> <molecule> + <coef> <molecule> + ... -> <coef> <molecule> + <coef> <molecule> + ...
-pub fn pretty_print_balanced(reaction: &(Vec<Molecule>, Vec<Molecule>), coefs: &Vec<u32>) {
-    let &(ref lhs, ref rhs) = reaction;
-    print!("{} {}", coefs[0], lhs[0]);
-    for (coef, molecule) in coefs.iter().zip(lhs.iter()).skip(1) {
-        print!(" + {} {}", coef, molecule);
-    }
-    print!(" -> ");
-    print!("{} {}", coefs[lhs.len()], rhs[0]);
-    for (coef, molecule) in coefs.iter().skip(lhs.len()).zip(rhs.iter()).skip(1) {
-        print!(" + {} {}", coef, molecule);
-    }
-    println!("");
+pub fn pretty_print_balanced(reaction: &Reaction) {
+    println!("{}", format_balanced(reaction));
+}
+
+/// Formats a balanced reaction in the same way as `pretty_print_balanced` prints it
+pub fn format_balanced(reaction: &Reaction) -> String {
+    let side = |species: &[Species]| {
+        let names: Vec<String> = species.iter().map(|s| {
+            format!("{} {}", s.coef, Formula(&s.molecule))
+        }).collect();
+        names.join(" + ")
+    };
+    format!("{} {} {}", side(&reaction.reactants), reaction.arrow_text(),
+            side(&reaction.products))
+}
+
+/// Makes the rows of a table with the coefficient of each species in a balanced reaction
+///
+/// The side of each species is either `reactant` or `product`.
+pub fn to_rows(reaction: &Reaction) -> Vec<Vec<String>> {
+    reaction.reactants.iter()
+            .map(|s| ("reactant", s))
+            .chain(reaction.products.iter().map(|s| ("product", s)))
+            .map(|(side, species)| {
+                vec!(side.to_string(), format!("{}", species.coef),
+                     format!("{}", Formula(&species.molecule)))
+            })
+            .collect()
+}
+
+/// Makes a JSON object with the balanced reaction, and the coefficient of each species
+pub fn to_json(reaction: &Reaction) -> Json {
+    let species = |species: &[Species]| {
+        Json::Array(species.iter().map(|s| {
+            Json::object(vec!(
+                ("formula", Json::string(Formula(&s.molecule))),
+                ("coefficient", Json::Number(s.coef as f64)),
+            ))
+        }).collect())
+    };
+    Json::object(vec!(
+        ("reaction", Json::String(format_balanced(reaction))),
+        ("reactants", species(&reaction.reactants)),
+        ("products", species(&reaction.products)),
+    ))
 }
 
 /// Balances a chemical reaction using Gaussian elimination and returns the coefficients
@@ -56,28 +97,33 @@ pub fn pretty_print_balanced(reaction: &(Vec<Molecule>, Vec<Molecule>), coefs: &
 /// * Each row corresponds to a periodic element.
 /// * The number at `R[i,j]` is the amount of periocic element `i` in molecule `j`.
 /// * Numbers from molecules on the right hand side of the equation will be negative.
+///
 /// Thus we can now solve the system to find a linear combination of the columns which will result
 /// in a zero-vector, and then read the coefficients from the solution.
-pub fn balance_reaction(reaction: &(Vec<Molecule>, Vec<Molecule>)) -> CTResult<Vec<u32>> {
+///
+/// The coefficients are in the same order as `Reaction::species`. Any coefficients already in
+/// the reaction are ignored.
+pub fn balance_reaction(reaction: &Reaction) -> CTResult<Vec<u32>> {
     let reac_mat = Matrix::from_reaction(reaction);
-    let reduced_mat = try!(forward_elim(reac_mat));
+    let reduced_mat = forward_elim(reac_mat)?;
     let coefs = back_substitute(&reduced_mat);
 
     // if any of the coefs are 0, then an element in that molecule is missing on the other side
-    // of the reaction
+    // of the reaction (the coefs are still in reverse order of the molecules here)
     if let Some(pos) = coefs.iter().position(|&c| c == 0.0) {
-        let &(ref lhs, ref rhs) = reaction;
-        let molecule = lhs.iter().chain(rhs.iter()).nth(pos).unwrap();
-        let begin = molecule.first().unwrap().pos;
-        let len = molecule.last().unwrap().pos + molecule.last().unwrap().len - begin;
-        return Err(CTError {
-            kind: InputError,
-            desc: format!("An element in {} is missing on the other side of the reaction",
-                          molecule),
-            pos: Some((begin, len)),
-        })
+        return Err(missing_element_error(reaction, coefs.len() - 1 - pos));
     }
 
+    // we also reverse the Vec so the coefs are in the right order
+    let coefs: Vec<f64> = coefs.into_iter().rev().collect();
+    Ok(to_integers(&coefs))
+}
+
+/// Scales the solution of the elimination to the smallest whole coefficients
+///
+/// Dividing by the smallest coefficient may still leave fractions, e.g. `[1, 1.5]` for
+/// `Fe^3+ + Zn`, so the smallest multiplier turning them all into integers is found as well.
+fn to_integers(coefs: &[f64]) -> Vec<u32> {
     // we find the minimum element with fold, since f64 does not implement Ord...
     let min = coefs.iter().fold(f64::INFINITY, |crnt, &num| {
         if num < crnt {
@@ -86,10 +132,105 @@ pub fn balance_reaction(reaction: &(Vec<Molecule>, Vec<Molecule>)) -> CTResult<V
             crnt
         }
     });
-    // now we divide all elements by the minimum to (hopefully) convert them all to intergers
-    // after which we do the actual conversion
-    // we also reverse the Vec so the coefs are in the right order
-    Ok(coefs.iter().rev().map(|n| (n / min) as u32).collect())
+    let is_whole = |n: f64| (n - n.round()).abs() < EPSILON;
+    let mult = (1..MAX_MULTIPLIER).find(|&m| coefs.iter().all(|n| is_whole(n / min * m as f64)))
+                                  .unwrap_or(1);
+    coefs.iter().map(|n| (n / min * mult as f64).round() as u32).collect()
+}
+
+/// Checks that the coefficients of the species in a reaction balance it
+///
+/// Every element, and the total charge, must be the same on both sides. The error names each
+/// element which is not, points at every species containing one of them, and gives the balanced
+/// reaction if there is one.
+pub fn check_balance(reaction: &Reaction) -> CTResult<()> {
+    // like when balancing, positive and negative charges are counted together as `+`
+    fn key(name: &str) -> &str {
+        if name == "-" { "+" } else { name }
+    }
+    let mut names = Vec::<&str>::new();
+    for species in reaction.species() {
+        for elem in species.molecule.iter() {
+            if !names.contains(&key(elem.name.as_str())) {
+                names.push(key(elem.name.as_str()));
+            }
+        }
+    }
+    let total = |side: &[Species], name: &str| {
+        side.iter().fold(0_f64, |t, s| t + s.coef as f64 * amount(&s.molecule, name))
+    };
+    let unbalanced: Vec<&str> = names.into_iter().filter(|&name| {
+        (total(&reaction.reactants, name) - total(&reaction.products, name)).abs() > EPSILON
+    }).collect();
+    if unbalanced.is_empty() {
+        return Ok(());
+    }
+
+    let describe = |names: Vec<&str>| {
+        let names: Vec<&str> = names.into_iter().map(|n| if n == "+" { "charge" } else { n })
+                                    .collect();
+        names.join(", ")
+    };
+    let labels = reaction.species().filter_map(|s| {
+        let found: Vec<&str> = unbalanced.iter().cloned().filter(|&name| {
+            s.molecule.iter().any(|e| key(e.name.as_str()) == name)
+        }).collect();
+        if found.is_empty() {
+            None
+        } else {
+            Some(Label { pos: s.pos, text: format!("contains {}", describe(found)) })
+        }
+    }).collect();
+    let desc = format!("The reaction is not balanced for: {}", describe(unbalanced.clone()));
+    let err = CTError::new(InputError, desc).with_labels(labels);
+    let mut balanced = reaction.clone();
+    Err(match balanced.balance() {
+        Ok(()) => err.with_help(format!("the balanced reaction is {}", format_balanced(&balanced))),
+        Err(_) => err,
+    })
+}
+
+/// Makes the error for when the molecule at the given index can not be part of the reaction
+///
+/// The error names the element which is missing on the other side, and points at every molecule
+/// containing it.
+fn missing_element_error(reaction: &Reaction, index: usize) -> CTError {
+    let (side, other, other_name) = if index < reaction.reactants.len() {
+        (&reaction.reactants, &reaction.products, "products")
+    } else {
+        (&reaction.products, &reaction.reactants, "reactants")
+    };
+    let molecule = &reaction.species().nth(index).unwrap().molecule;
+    let contains = |m: &Molecule, name: &str| m.iter().any(|e| e.name.as_str() == name);
+    let missing = molecule.iter().find(|e| {
+        !e.is_charge() && !other.iter().any(|s| contains(&s.molecule, e.name.as_str()))
+    });
+
+    match missing {
+        Some(elem) => {
+            let desc = format!("The element {} in {} is missing on the other side of the reaction",
+                               elem.name, Formula(molecule));
+            let labels = side.iter().filter(|s| contains(&s.molecule, elem.name.as_str())).map(|s| {
+                Label { pos: span(&s.molecule), text: format!("contains {}", elem.name) }
+            }).collect();
+            CTError::new(InputError, desc)
+                .with_pos(span(molecule))
+                .with_labels(labels)
+                .with_help(format!("add a molecule containing {} to the {}", elem.name, other_name))
+        },
+        None => {
+            let desc = format!("An element in {} is missing on the other side of the reaction",
+                               Formula(molecule));
+            CTError::new(InputError, desc).with_pos(span(molecule))
+        },
+    }
+}
+
+/// Finds the position and length of a molecule in the input
+fn span(molecule: &Molecule) -> (usize, usize) {
+    let begin = molecule.first().unwrap().pos;
+    let last = molecule.last().unwrap();
+    (begin, last.pos + last.len - begin)
 }
 
 fn column_abs_max_index(columns: &[Vec<f64>], column: usize) -> usize {
@@ -106,12 +247,15 @@ fn forward_elim(mut mat: Matrix) -> CTResult<Matrix> {
     for k in 0..min(mat.width(), mat.height()) {
         // locate the pivot
         let pivot = column_abs_max_index(&mat[k..], k) + k;
-        if mat[pivot][k] == 0_f64 {
-            return Err(CTError {
-                kind: InputError,
-                desc: "Could not balance reaction".to_string(),
-                pos: None,
-            })
+        if mat[pivot][k].abs() < EPSILON {
+            // when an element is redundant, e.g. because it always appears together with another
+            // element, the rest of the rows end up as zeros, and the remaining coefs are free
+            if mat[k..].iter().all(|row| row.iter().all(|x| x.abs() < EPSILON)) {
+                mat.buf.truncate(k);
+                mat.height = k;
+                break;
+            }
+            return Err(CTError::new(InputError, "Could not balance reaction"))
         }
         // move the pivot to its new position
         mat.switch_rows(k, pivot);
@@ -143,6 +287,19 @@ fn back_substitute(mat: &Matrix) -> Vec<f64> {
     vars
 }
 
+/// Finds the amount of the named element in a grouped molecule
+///
+/// The amount of `+` is the charge of the molecule, which is negative for a negative charge.
+fn amount(molecule: &Molecule, name: &str) -> f64 {
+    molecule.iter().fold(0_f64, |total, e| {
+        match e.name.as_str() {
+            n if n == name => total + e.coef as f64,
+            "-" if name == "+" => total - e.coef as f64,
+            _ => total,
+        }
+    })
+}
+
 #[derive(Debug, PartialEq)]
 struct Matrix {
     buf: Vec<Vec<f64>>,
@@ -151,16 +308,20 @@ struct Matrix {
 }
 
 impl Matrix {
-    fn from_reaction(reaction: &(Vec<Molecule>, Vec<Molecule>)) -> Matrix {
-        let &(ref lhs, ref rhs) = reaction;
-        let lhs: Vec<Molecule> = lhs.clone().into_iter().map(|m| elem::group_elems(m)).collect();
-        let rhs: Vec<Molecule> = rhs.clone().into_iter().map(|m| elem::group_elems(m)).collect();
+    fn from_reaction(reaction: &Reaction) -> Matrix {
+        let group = |side: &[Species]| -> Vec<Molecule> {
+            side.iter().map(|s| elem::group_elems(s.molecule.clone())).collect()
+        };
+        let lhs = group(&reaction.reactants);
+        let rhs = group(&reaction.products);
         let mut names = Vec::<&str>::new();
         // gather up all the element names in the reaction so we now how many rows will be needed
+        // positive and negative charges share a single row, since the charge must be conserved
         for molecule in lhs.iter().chain(rhs.iter()) {
             for elem in molecule.iter() {
-                if names.iter().find(|e| **e == elem.name).is_none() {
-                    names.push(elem.name.as_slice());
+                let name = if elem.is_charge() { "+" } else { elem.name.as_str() };
+                if names.iter().find(|e| **e == name).is_none() {
+                    names.push(name);
                 }
             }
         }
@@ -168,26 +329,20 @@ impl Matrix {
         for name in names.iter() {
             let mut row = Vec::with_capacity(lhs.len() + rhs.len());
             for molecule in lhs.iter() {
-                row.push(molecule.iter()
-                                 .find(|e| e.name == *name)
-                                 .and_then(|e| Some(e.coef as f64))
-                                 .unwrap_or(0_f64));
+                row.push(amount(molecule, name));
             }
             // we loop over rhs seperately, since we need to multiply the coefs with -1
             for molecule in rhs.iter() {
-                row.push(molecule.iter()
-                                 .find(|e| e.name == *name)
-                                 .and_then(|e| Some(-1.0*(e.coef as f64)))
-                                 .unwrap_or(0_f64));
+                row.push(-amount(molecule, name));
             }
             buf.push(row);
         }
         let height = names.len();
         let width = lhs.len() + rhs.len();
         Matrix {
-            buf: buf,
-            height: height,
-            width: width,
+            buf,
+            height,
+            width,
         }
     }
 
@@ -226,8 +381,8 @@ impl_matrix_index_mut!(RangeFull, [Vec<f64>]);
 #[cfg(test)]
 mod test {
     use super::*;
-    use elem::PerElem;
-    use error::CTErrorKind::InputError;
+    use crate::elem::PerElem;
+    use crate::error::CTErrorKind::InputError;
 
     macro_rules! dummy_elem(
         ($name:expr) => (
@@ -241,10 +396,10 @@ mod test {
     #[test]
     fn balance() {
         // attempt to balance C3H8 + O2 -> CO2 + H2O
-        let reaction = (vec!(vec!(dummy_elem!("C", 3), dummy_elem!("H", 8)),
-                             vec!(dummy_elem!("O", 2))),
-                        vec!(vec!(dummy_elem!("C", 1), dummy_elem!("O", 2)),
-                             vec!(dummy_elem!("H", 2), dummy_elem!("O", 1))));
+        let reaction = Reaction::new(vec!(vec!(dummy_elem!("C", 3), dummy_elem!("H", 8)),
+                                          vec!(dummy_elem!("O", 2))),
+                                     vec!(vec!(dummy_elem!("C", 1), dummy_elem!("O", 2)),
+                                          vec!(dummy_elem!("H", 2), dummy_elem!("O", 1))));
         let result = balance_reaction(&reaction);
         let expected = Ok(vec!(1, 5, 3, 4));
         assert_eq!(result, expected);
@@ -252,11 +407,11 @@ mod test {
 
     #[test]
     fn balance_non_grouped() {
-        let reaction = (vec!(vec!(dummy_elem!("C"), dummy_elem!("H", 3), dummy_elem!("C"),
-                                  dummy_elem!("H", 2), dummy_elem!("C"), dummy_elem!("H", 3)),
-                             vec!(dummy_elem!("O", 2))),
-                        vec!(vec!(dummy_elem!("C", 1), dummy_elem!("O", 2)),
-                             vec!(dummy_elem!("H", 2), dummy_elem!("O", 1))));
+        let propane = vec!(dummy_elem!("C"), dummy_elem!("H", 3), dummy_elem!("C"),
+                           dummy_elem!("H", 2), dummy_elem!("C"), dummy_elem!("H", 3));
+        let reaction = Reaction::new(vec!(propane, vec!(dummy_elem!("O", 2))),
+                                     vec!(vec!(dummy_elem!("C", 1), dummy_elem!("O", 2)),
+                                          vec!(dummy_elem!("H", 2), dummy_elem!("O", 1))));
         let result = balance_reaction(&reaction);
         let expected = Ok(vec!(1, 5, 3, 4));
         assert_eq!(result, expected);
@@ -264,8 +419,8 @@ mod test {
 
     #[test]
     fn no_balance_needed() {
-        let reaction = (vec!(vec!(dummy_elem!("C", 1)), vec!(dummy_elem!("H", 1))),
-                        vec!(vec!(dummy_elem!("C", 1)), vec!(dummy_elem!("H", 1))));
+        let reaction = Reaction::new(vec!(vec!(dummy_elem!("C", 1)), vec!(dummy_elem!("H", 1))),
+                                     vec!(vec!(dummy_elem!("C", 1)), vec!(dummy_elem!("H", 1))));
         let result = balance_reaction(&reaction);
         let expected = Ok(vec!(1, 1, 1, 1));
         assert_eq!(result, expected);
@@ -273,11 +428,103 @@ mod test {
 
     #[test]
     fn missing_elem() {
-        let reaction = (vec!(vec!(dummy_elem!("C", 1)), vec!(dummy_elem!("H", 1))),
-                        vec!(vec!(dummy_elem!("C", 1))));
+        let reaction = Reaction::new(vec!(vec!(dummy_elem!("C", 1)), vec!(dummy_elem!("H", 1))),
+                                     vec!(vec!(dummy_elem!("C", 1))));
         let result = balance_reaction(&reaction);
         println!("{:?}", result);
         assert!(result.is_err());
         assert_eq!(result.err().unwrap().kind, InputError);
     }
+
+    #[test]
+    fn missing_elem_named() {
+        let reaction = Reaction::new(vec!(vec!(dummy_elem!("C", 1)), vec!(dummy_elem!("H", 1))),
+                                     vec!(vec!(dummy_elem!("C", 1))));
+        let err = balance_reaction(&reaction).err().unwrap();
+        assert!(err.desc.contains("The element H"));
+        assert_eq!(err.labels.len(), 1);
+        assert_eq!(err.help, Some("add a molecule containing H to the products".to_string()));
+    }
+
+    #[test]
+    fn balance_charges() {
+        // attempt to balance Fe^3+ + Zn -> Fe + Zn^2+
+        let reaction = Reaction::new(vec!(vec!(dummy_elem!("Fe"), dummy_elem!("+", 3)),
+                                          vec!(dummy_elem!("Zn"))),
+                                     vec!(vec!(dummy_elem!("Fe")),
+                                          vec!(dummy_elem!("Zn"), dummy_elem!("+", 2))));
+        let result = balance_reaction(&reaction);
+        let expected = Ok(vec!(2, 3, 2, 3));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn integer_coefs() {
+        assert_eq!(to_integers(&[1.0, 1.5, 1.0, 1.5]), vec!(2, 3, 2, 3));
+        assert_eq!(to_integers(&[0.5, 0.25, 1.0 / 3.0]), vec!(6, 3, 4));
+        // the elimination leaves tiny rounding errors, which must not count as fractions
+        assert_eq!(to_integers(&[1.0, 2.0 + 1e-12, 2.0 - 1e-12]), vec!(1, 2, 2));
+    }
+
+    #[test]
+    fn balance_redundant_element() {
+        // attempt to balance CaCO3 -> CaO + CO2, where the row for O ends up as zeros, since Ca
+        // and C already fix every coefficient
+        let reaction = Reaction::new(vec!(vec!(dummy_elem!("Ca"), dummy_elem!("C"),
+                                               dummy_elem!("O", 3))),
+                                     vec!(vec!(dummy_elem!("Ca"), dummy_elem!("O")),
+                                          vec!(dummy_elem!("C"), dummy_elem!("O", 2))));
+        let result = balance_reaction(&reaction);
+        let expected = Ok(vec!(1, 1, 1));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn balance_opposite_charges() {
+        // attempt to balance Ag^+ + Cl^- -> AgCl
+        let reaction = Reaction::new(vec!(vec!(dummy_elem!("Ag"), dummy_elem!("+")),
+                                          vec!(dummy_elem!("Cl"), dummy_elem!("-"))),
+                                     vec!(vec!(dummy_elem!("Ag"), dummy_elem!("Cl"))));
+        let result = balance_reaction(&reaction);
+        let expected = Ok(vec!(1, 1, 1));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn check_balanced() {
+        let mut reaction = Reaction::new(vec!(vec!(dummy_elem!("H", 2)), vec!(dummy_elem!("O", 2))),
+                                         vec!(vec!(dummy_elem!("H", 2), dummy_elem!("O", 1))));
+        reaction.set_coefs(&[2, 1, 2]);
+        assert_eq!(check_balance(&reaction), Ok(()));
+    }
+
+    #[test]
+    fn check_unbalanced() {
+        let reaction = Reaction::new(vec!(vec!(dummy_elem!("H", 2)), vec!(dummy_elem!("O", 2))),
+                                     vec!(vec!(dummy_elem!("H", 2), dummy_elem!("O", 1))));
+        let err = check_balance(&reaction).err().unwrap();
+        assert_eq!(err.desc, "The reaction is not balanced for: O");
+        assert_eq!(err.labels.len(), 2);
+        assert_eq!(err.help, Some("the balanced reaction is 2 H2 + 1 O2 -> 2 H2O".to_string()));
+    }
+
+    #[test]
+    fn check_charge() {
+        let reaction = Reaction::new(vec!(vec!(dummy_elem!("Fe"), dummy_elem!("+", 3))),
+                                     vec!(vec!(dummy_elem!("Fe"), dummy_elem!("+", 2))));
+        let err = check_balance(&reaction).err().unwrap();
+        assert_eq!(err.desc, "The reaction is not balanced for: charge");
+    }
+
+    #[test]
+    fn rows() {
+        let mut reaction = Reaction::new(vec!(vec!(dummy_elem!("H", 2)), vec!(dummy_elem!("O", 2))),
+                                         vec!(vec!(dummy_elem!("H", 2), dummy_elem!("O", 1))));
+        reaction.set_coefs(&[2, 1, 2]);
+        let result = to_rows(&reaction);
+        let expected = vec!(vec!("reactant".to_string(), "2".to_string(), "H2".to_string()),
+                            vec!("reactant".to_string(), "1".to_string(), "O2".to_string()),
+                            vec!("product".to_string(), "2".to_string(), "H2O".to_string()));
+        assert_eq!(result, expected);
+    }
 }
\ No newline at end of file
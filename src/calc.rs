@@ -0,0 +1,396 @@
+//! An evaluator for arithmetic expressions over molar masses and quantities with units.
+//!
+//! The evaluator works with the following grammar:
+//! S = (S)um
+//! P = (P)roduct
+//! F = (F)actor
+//! Q = (Q)uantity
+//!
+//! S --> P + S | P - S
+//!    |  P
+//! P --> F * P | F / P
+//!    |  F
+//! F --> -F
+//!    |  (S)
+//!    |  M(<formula>)
+//!    |  Q
+//!    |  <unit>
+//! Q --> <number> <unit>
+//!    |  <number>
+//!
+//! `M(<formula>)` is the molar mass of the formula, in g/mol. The units which may follow a number
+//! are those of masses, amounts of substance, volumes and molar concentrations, e.g. `g`, `mol`,
+//! `mL` or `M`, and the unit of the result is worked out from them, e.g. `M(H2O) * 2 mol` is a
+//! mass in grams. A unit on its own means one of that unit, so `2 mol/L` is the same as
+//! `2 mol / 1 L`.
+
+use std::fmt::{Display, Formatter, Error};
+use crate::parser;
+use crate::elem;
+use crate::database::ElemDatabase;
+use crate::compounds::CompoundDatabase;
+use crate::units;
+use crate::units::Unit;
+use crate::mass;
+use crate::error::{CTResult, CTError};
+use crate::error::CTErrorKind::InputError;
+
+/// The exponents of grams, moles and litres in a unit, e.g. [1, -1, 0] for g/mol
+pub type Dims = [i32; 3];
+
+static DIM_NAMES: [&str; 3] = ["g", "mol", "L"];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Quantity {
+    /// The value, in the base units (grams, moles and litres)
+    pub value: f64,
+    pub dims: Dims,
+}
+
+impl Quantity {
+    /// Formats the unit of the quantity, e.g. `g/mol`, or an empty string if it has no unit
+    pub fn unit_name(&self) -> String {
+        let mut num = Vec::new();
+        let mut den = Vec::new();
+        for (&name, &exp) in DIM_NAMES.iter().zip(self.dims.iter()) {
+            let part = if exp.abs() == 1 {
+                name.to_string()
+            } else {
+                format!("{}^{}", name, exp.abs())
+            };
+            if exp > 0 {
+                num.push(part);
+            } else if exp < 0 {
+                den.push(part);
+            }
+        }
+        match (num.is_empty(), den.is_empty()) {
+            (_, true) => num.join("·"),
+            (true, false) => format!("1/{}", den.join("·")),
+            (false, false) => format!("{}/{}", num.join("·"), den.join("·")),
+        }
+    }
+}
+
+impl Display for Quantity {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        let unit = self.unit_name();
+        if unit.is_empty() {
+            write!(fmt, "{}", self.value)
+        } else {
+            write!(fmt, "{} {}", self.value, unit)
+        }
+    }
+}
+
+/// Evaluates an expression, using the given function to find the molar mass of a formula
+pub fn evaluate<F>(input: &str, molar_mass: F) -> CTResult<Quantity>
+    where F: FnMut(&str) -> CTResult<f64> {
+    let mut eval = Evaluator { input, pos: 0, molar_mass };
+    let result = eval.parse_sum()?;
+    eval.consume_whitespace();
+    if eval.pos < input.len() {
+        Err(CTError::new(InputError, "Unexpected character")
+            .with_pos((eval.pos, 1)))
+    } else {
+        Ok(result)
+    }
+}
+
+/// Finds the molar mass of a formula, which may also be the name of a compound
+///
+/// The name is only looked up if the formula does not parse, and a compound database is given.
+pub fn molar_mass(database: &mut ElemDatabase, compounds: Option<&CompoundDatabase>,
+                  formula: &str) -> CTResult<f64> {
+    let formula = match compounds {
+        Some(db) => db.resolve_molecule(formula, database).0,
+        None => formula.to_string(),
+    };
+    let molecule = elem::strip_charge(parser::parse_single_molecule(formula.as_str())?);
+    let data = database.get_data(&molecule)?;
+    Ok(mass::molar_mass(&data, &molecule))
+}
+
+struct Evaluator<'a, F> {
+    input: &'a str,
+    pos: usize,
+    molar_mass: F,
+}
+
+impl<'a, F> Evaluator<'a, F> where F: FnMut(&str) -> CTResult<f64> {
+    fn parse_sum(&mut self) -> CTResult<Quantity> {
+        let mut out = self.parse_product()?;
+        loop {
+            self.consume_whitespace();
+            let op_pos = self.pos;
+            let sign = match self.peek_char() {
+                Some('+') => 1.0,
+                Some('-') => -1.0,
+                _ => return Ok(out),
+            };
+            self.pos += 1;
+            let rhs = self.parse_product()?;
+            if rhs.dims != out.dims {
+                let desc = format!("Can not add or subtract quantities with different units: {:?} \
+                                    and {:?}", out.unit_name(), rhs.unit_name());
+                return Err(CTError::new(InputError, desc).with_pos((op_pos, 1)));
+            }
+            out.value += sign * rhs.value;
+        }
+    }
+
+    fn parse_product(&mut self) -> CTResult<Quantity> {
+        let mut out = self.parse_factor()?;
+        loop {
+            self.consume_whitespace();
+            // dividing is the same as multiplying, except that the exponents are subtracted
+            let sign = match self.peek_char() {
+                Some('*') => 1,
+                Some('/') => -1,
+                _ => return Ok(out),
+            };
+            self.pos += 1;
+            let rhs = self.parse_factor()?;
+            if sign > 0 {
+                out.value *= rhs.value;
+            } else {
+                out.value /= rhs.value;
+            }
+            for (d, r) in out.dims.iter_mut().zip(rhs.dims.iter()) {
+                *d += sign * *r;
+            }
+        }
+    }
+
+    fn parse_factor(&mut self) -> CTResult<Quantity> {
+        self.consume_whitespace();
+        let start_pos = self.pos;
+        match self.peek_char() {
+            Some('-') => {
+                self.pos += 1;
+                let factor = self.parse_factor()?;
+                Ok(Quantity { value: -factor.value, dims: factor.dims })
+            },
+            Some('(') => {
+                self.pos += 1;
+                let sum = self.parse_sum()?;
+                self.consume_whitespace();
+                if self.peek_char() == Some(')') {
+                    self.pos += 1;
+                    Ok(sum)
+                } else {
+                    Err(CTError::new(InputError, "Missing closing parentheses")
+                        .with_pos((start_pos, 1)))
+                }
+            },
+            Some('M') if self.input[self.pos..].starts_with("M(") => self.parse_molar_mass(),
+            Some(ch) if ch.is_numeric() || ch == '.' => self.parse_quantity(),
+            Some(ch) if ch.is_alphabetic() => self.parse_unit(1.0),
+            _ => Err(CTError::new(InputError,
+                                  "Expected a number, unit, M(<formula>) or opening parenthesis")
+                .with_pos((start_pos, 1))),
+        }
+    }
+
+    fn parse_molar_mass(&mut self) -> CTResult<Quantity> {
+        // skip past the 'M('
+        let open_pos = self.pos + 1;
+        self.pos += 2;
+        let start_pos = self.pos;
+        // the formula may contain parentheses itself, so we have to find the matching one
+        let mut level = 0;
+        loop {
+            match self.peek_char() {
+                Some(')') if level == 0 => break,
+                Some(ch) => {
+                    if ch == '(' {
+                        level += 1;
+                    } else if ch == ')' {
+                        level -= 1;
+                    }
+                    self.pos += ch.len_utf8();
+                },
+                None => return Err(CTError::new(InputError, "Missing closing parentheses")
+                    .with_pos((open_pos, 1))),
+            }
+        }
+        let formula = &self.input[start_pos..self.pos];
+        self.pos += 1;
+        let value = (self.molar_mass)(formula).map_err(|e| e.shift(start_pos))?;
+        Ok(Quantity { value, dims: [1, -1, 0] })
+    }
+
+    fn parse_quantity(&mut self) -> CTResult<Quantity> {
+        let start_pos = self.pos;
+        self.consume_while(|ch| ch.is_numeric() || ch == '.');
+        // an exponent is only consumed if it is complete, since 'e' could otherwise be a unit
+        let rest = &self.input[self.pos..];
+        let exp_len = if rest.starts_with("e") || rest.starts_with("E") {
+            let sign_len = match rest[1..].chars().next() {
+                Some('-') | Some('+') => 1,
+                _ => 0,
+            };
+            let digits = rest[1 + sign_len..].chars().take_while(|ch| ch.is_numeric()).count();
+            if digits > 0 { 1 + sign_len + digits } else { 0 }
+        } else {
+            0
+        };
+        self.pos += exp_len;
+        let num_str = &self.input[start_pos..self.pos];
+        let value = match num_str.parse::<f64>() {
+            Ok(value) => value,
+            Err(e) => return Err(CTError::new(InputError, "Could not parse number")
+                .with_pos((start_pos, num_str.len()))
+                .with_source(e)),
+        };
+
+        // the unit is optional, so if there is none we must not consume the whitespace before
+        // whatever comes next
+        let num_end = self.pos;
+        self.consume_whitespace();
+        if self.peek_char().is_some_and(|ch| ch.is_alphabetic()) &&
+           !self.input[self.pos..].starts_with("M(") {
+            self.parse_unit(value)
+        } else {
+            self.pos = num_end;
+            Ok(Quantity { value, dims: [0, 0, 0] })
+        }
+    }
+
+    /// Parses a unit, and converts the value given in that unit to the base units
+    fn parse_unit(&mut self, value: f64) -> CTResult<Quantity> {
+        let unit_pos = self.pos;
+        let unit = self.consume_while(|ch| ch.is_alphanumeric() || ch == '³');
+        match find_unit(unit.as_str()) {
+            Some((factor, dims)) => Ok(Quantity { value: value * factor, dims }),
+            None => Err(CTError::new(InputError, format!("Unknown unit: {:?}", unit))
+                .with_pos((unit_pos, unit.len()))),
+        }
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn consume_while<P>(&mut self, pred: P) -> String where P: Fn(char) -> bool {
+        let mut out = String::new();
+        while let Some(ch) = self.peek_char() {
+            if !pred(ch) {
+                break;
+            }
+            out.push(ch);
+            self.pos += ch.len_utf8();
+        }
+        out
+    }
+
+    fn consume_whitespace(&mut self) {
+        self.consume_while(|ch| ch.is_whitespace());
+    }
+}
+
+/// Finds the factor which converts the unit to the base units, along with its dimensions
+fn find_unit(name: &str) -> Option<(f64, Dims)> {
+    let tables: [(&[Unit], Dims); 4] = [
+        (units::MASS, [1, 0, 0]),
+        (units::AMOUNT, [0, 1, 0]),
+        (units::VOLUME, [0, 0, 1]),
+        (units::MOLARITY, [0, 1, -1]),
+    ];
+    for &(table, dims) in tables.iter() {
+        if let Some(&(_, factor)) = table.iter().find(|&&(n, _)| n == name) {
+            return Some((factor, dims));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::{CTResult, CTError};
+    use crate::error::CTErrorKind::InputError;
+
+    // a stand-in for the database, which only knows a few formulas
+    fn dummy_mass(formula: &str) -> CTResult<f64> {
+        match formula {
+            "H2O" => Ok(18.0),
+            "NaCl" => Ok(58.5),
+            "Ca(OH)2" => Ok(74.0),
+            _ => Err(CTError::new(InputError, "Unknown formula")
+                .with_pos((0, formula.len()))),
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        let result = evaluate("2*M(H2O) + M(NaCl)/3", dummy_mass).unwrap();
+        assert_eq!(result, Quantity { value: 55.5, dims: [1, -1, 0] });
+    }
+
+    #[test]
+    fn precedence() {
+        let result = evaluate("1 + 2 * 3 - (4 - 2) / 2", dummy_mass).unwrap();
+        assert_eq!(result.value, 6.0);
+    }
+
+    #[test]
+    fn units() {
+        let result = evaluate("M(H2O) * 0.5 mol", dummy_mass).unwrap();
+        assert_eq!(result, Quantity { value: 9.0, dims: [1, 0, 0] });
+        assert_eq!(result.unit_name(), "g");
+    }
+
+    #[test]
+    fn unit_prefix() {
+        let result = evaluate("250 mL * 0.1 M", dummy_mass).unwrap();
+        assert!((result.value - 0.025).abs() < 1e-12);
+        assert_eq!(result.unit_name(), "mol");
+    }
+
+    #[test]
+    fn bare_unit() {
+        let result = evaluate("2 mol/L", dummy_mass).unwrap();
+        assert_eq!(result, Quantity { value: 2.0, dims: [0, 1, -1] });
+    }
+
+    #[test]
+    fn exponent() {
+        assert_eq!(evaluate("1e3", dummy_mass).unwrap().value, 1000.0);
+    }
+
+    #[test]
+    fn nested_parens() {
+        assert_eq!(evaluate("M(Ca(OH)2)", dummy_mass).unwrap().value, 74.0);
+    }
+
+    #[test]
+    fn unit_names() {
+        assert_eq!(Quantity { value: 1.0, dims: [1, -1, 0] }.unit_name(), "g/mol");
+        assert_eq!(Quantity { value: 1.0, dims: [0, 0, -1] }.unit_name(), "1/L");
+        assert_eq!(format!("{}", Quantity { value: 2.0, dims: [0, 0, 0] }), "2");
+    }
+
+    #[test]
+    fn different_units() {
+        let result = evaluate("M(H2O) + 1 g", dummy_mass);
+        assert_eq!(result.err().unwrap().pos, Some((7, 1)));
+    }
+
+    #[test]
+    fn formula_error_pos() {
+        let result = evaluate("2 * M(Xx)", dummy_mass);
+        assert_eq!(result.err().unwrap().pos, Some((6, 2)));
+    }
+
+    #[test]
+    fn unknown_unit() {
+        let result = evaluate("2 parsecs", dummy_mass);
+        assert_eq!(result.err().unwrap().pos, Some((2, 7)));
+    }
+
+    #[test]
+    fn trailing_input() {
+        assert!(evaluate("2 3", dummy_mass).is_err());
+    }
+}
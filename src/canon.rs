@@ -0,0 +1,154 @@
+//! Functions for writing formulas in a canonical element order.
+//!
+//! Two orders are supported:
+//! * Hill order, where carbon comes first, then hydrogen, and then the rest alphabetically. If
+//!   there is no carbon, all elements (including hydrogen) are sorted alphabetically.
+//! * Electronegativity order, where the elements are sorted from the most electropositive to the
+//!   most electronegative, e.g. NaCl or H2SO4. Elements without a known electronegativity are
+//!   put last.
+//!
+//! By default organic compounds (those containing carbon) use Hill order, and inorganic
+//! compounds use electronegativity order.
+
+use std::cmp::Ordering;
+use crate::elem;
+use crate::elem::{PerElem, Molecule};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Order {
+    Hill,
+    Electronegativity,
+}
+
+/// Pauling electronegativities of the elements
+static ELECTRONEGATIVITIES: [(&str, f64); 100] = [
+    ("H", 2.20), ("Li", 0.98), ("Be", 1.57), ("B", 2.04), ("C", 2.55), ("N", 3.04),
+    ("O", 3.44), ("F", 3.98), ("Na", 0.93), ("Mg", 1.31), ("Al", 1.61), ("Si", 1.90),
+    ("P", 2.19), ("S", 2.58), ("Cl", 3.16), ("K", 0.82), ("Ca", 1.00), ("Sc", 1.36),
+    ("Ti", 1.54), ("V", 1.63), ("Cr", 1.66), ("Mn", 1.55), ("Fe", 1.83), ("Co", 1.88),
+    ("Ni", 1.91), ("Cu", 1.90), ("Zn", 1.65), ("Ga", 1.81), ("Ge", 2.01), ("As", 2.18),
+    ("Se", 2.55), ("Br", 2.96), ("Kr", 3.00), ("Rb", 0.82), ("Sr", 0.95), ("Y", 1.22),
+    ("Zr", 1.33), ("Nb", 1.6), ("Mo", 2.16), ("Tc", 1.9), ("Ru", 2.2), ("Rh", 2.28),
+    ("Pd", 2.20), ("Ag", 1.93), ("Cd", 1.69), ("In", 1.78), ("Sn", 1.96), ("Sb", 2.05),
+    ("Te", 2.1), ("I", 2.66), ("Xe", 2.6), ("Cs", 0.79), ("Ba", 0.89), ("La", 1.10),
+    ("Ce", 1.12), ("Pr", 1.13), ("Nd", 1.14), ("Pm", 1.13), ("Sm", 1.17), ("Eu", 1.2),
+    ("Gd", 1.2), ("Tb", 1.1), ("Dy", 1.22), ("Ho", 1.23), ("Er", 1.24), ("Tm", 1.25),
+    ("Yb", 1.1), ("Lu", 1.27), ("Hf", 1.3), ("Ta", 1.5), ("W", 2.36), ("Re", 1.9),
+    ("Os", 2.2), ("Ir", 2.20), ("Pt", 2.28), ("Au", 2.54), ("Hg", 2.00), ("Tl", 1.62),
+    ("Pb", 2.33), ("Bi", 2.02), ("Po", 2.0), ("At", 2.2), ("Rn", 2.2), ("Fr", 0.7),
+    ("Ra", 0.9), ("Ac", 1.1), ("Th", 1.3), ("Pa", 1.5), ("U", 1.38), ("Np", 1.36),
+    ("Pu", 1.28), ("Am", 1.13), ("Cm", 1.28), ("Bk", 1.3), ("Cf", 1.3), ("Es", 1.3),
+    ("Fm", 1.3), ("Md", 1.3), ("No", 1.3), ("Lr", 1.3),
+];
+
+/// Finds the order which should be used for the given molecule
+///
+/// Molecules containing carbon use Hill order, and all others use electronegativity order.
+pub fn default_order(molecule: &Molecule) -> Order {
+    if molecule.iter().any(|e| e.name == "C") {
+        Order::Hill
+    } else {
+        Order::Electronegativity
+    }
+}
+
+/// Groups the elements of a molecule and sorts them in the given order
+pub fn canonicalize(molecule: Molecule, order: Order) -> Molecule {
+    let mut molecule = elem::group_elems(molecule);
+    match order {
+        Order::Hill => {
+            let has_carbon = molecule.iter().any(|e| e.name == "C");
+            molecule.as_mut_slice().sort_by(|a, b| hill_cmp(a, b, has_carbon));
+        },
+        Order::Electronegativity => {
+            molecule.as_mut_slice().sort_by(electronegativity_cmp);
+        },
+    }
+    molecule
+}
+
+fn hill_cmp(a: &PerElem, b: &PerElem, has_carbon: bool) -> Ordering {
+    // without carbon everything is alphabetical, which is how the molecule is already sorted
+    let rank = |e: &PerElem| match e.name.as_str() {
+        "C" if has_carbon => 0,
+        "H" if has_carbon => 1,
+        _ => 2,
+    };
+    match rank(a).cmp(&rank(b)) {
+        Ordering::Equal => a.name.cmp(&b.name),
+        ord => ord,
+    }
+}
+
+fn electronegativity_cmp(a: &PerElem, b: &PerElem) -> Ordering {
+    match (electronegativity(a.name.as_str()), electronegativity(b.name.as_str())) {
+        (Some(x), Some(y)) if x != y => x.partial_cmp(&y).unwrap(),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        _ => a.name.cmp(&b.name),
+    }
+}
+
+fn electronegativity(name: &str) -> Option<f64> {
+    ELECTRONEGATIVITIES.iter().find(|&&(n, _)| n == name).map(|&(_, en)| en)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elem::PerElem;
+
+    macro_rules! dummy_elem(
+        ($name:expr, $coef:expr) => (
+            PerElem { name: $name.to_string(), coef: $coef, pos: 0, len: 1 }
+        );
+    );
+
+    fn names(molecule: &[PerElem]) -> Vec<&str> {
+        molecule.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    #[test]
+    fn hill() {
+        let molecule = vec!(dummy_elem!("Br", 1), dummy_elem!("C", 6), dummy_elem!("H", 5));
+        let result = canonicalize(molecule, Order::Hill);
+        assert_eq!(names(&result), vec!("C", "H", "Br"));
+    }
+
+    #[test]
+    fn hill_groups() {
+        let molecule = vec!(dummy_elem!("C", 1), dummy_elem!("H", 3), dummy_elem!("C", 1),
+                            dummy_elem!("H", 2), dummy_elem!("O", 1), dummy_elem!("H", 1));
+        let result = canonicalize(molecule, Order::Hill);
+        let expected = vec!(dummy_elem!("C", 2), dummy_elem!("H", 6), dummy_elem!("O", 1));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn hill_without_carbon() {
+        let molecule = vec!(dummy_elem!("H", 2), dummy_elem!("S", 1), dummy_elem!("O", 4));
+        let result = canonicalize(molecule, Order::Hill);
+        assert_eq!(names(&result), vec!("H", "O", "S"));
+    }
+
+    #[test]
+    fn electronegativity() {
+        let molecule = vec!(dummy_elem!("O", 4), dummy_elem!("S", 1), dummy_elem!("H", 2));
+        let result = canonicalize(molecule, Order::Electronegativity);
+        assert_eq!(names(&result), vec!("H", "S", "O"));
+    }
+
+    #[test]
+    fn unknown_electronegativity_last() {
+        let molecule = vec!(dummy_elem!("Xx", 1), dummy_elem!("F", 2));
+        let result = canonicalize(molecule, Order::Electronegativity);
+        assert_eq!(names(&result), vec!("F", "Xx"));
+    }
+
+    #[test]
+    fn default() {
+        assert_eq!(default_order(&vec!(dummy_elem!("C", 1), dummy_elem!("O", 2))), Order::Hill);
+        assert_eq!(default_order(&vec!(dummy_elem!("Na", 1), dummy_elem!("Cl", 1))),
+                   Order::Electronegativity);
+    }
+}
@@ -0,0 +1,251 @@
+//! Functions for looking up compounds by name, e.g. `water` or `carbon dioxide`.
+//!
+//! The names are read from a database where each line has the format `<name>;<formula>`. A
+//! compound may appear on several lines to give it more than one name. Names are matched without
+//! regard to case or repeated whitespace. A species which parses as a formula is only looked up
+//! by name if one of its elements is not in the element database, e.g. `Water`.
+
+use std::fs;
+use std::path::Path;
+use crate::elem;
+use crate::parser;
+use crate::parser::Parser;
+use crate::database::ElemDatabase;
+use crate::error::{CTResult, CTError};
+use crate::error::CTErrorKind::{InputError, DatabaseError};
+
+pub struct CompoundDatabase {
+    entries: Vec<(String, String)>,
+}
+
+impl CompoundDatabase {
+    /// Try to read the database in the file at the given path
+    pub fn open(path: &Path) -> CTResult<CompoundDatabase> {
+        match fs::read_to_string(path) {
+            Ok(contents) => CompoundDatabase::from_csv(contents.as_str()),
+            Err(e) => {
+                let desc = format!("Could not open compound database file. Expected at: {:?}",
+                                   path.to_str().unwrap_or("same directory as the program"));
+                Err(CTError::new(DatabaseError, desc).with_source(e))
+            },
+        }
+    }
+
+    /// Try to make the database from the contents of a database file
+    pub fn from_csv(contents: &str) -> CTResult<CompoundDatabase> {
+        let mut entries = Vec::new();
+        for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+            let data: Vec<&str> = line.trim().split(';').collect();
+            if data.len() < 2 {
+                return Err(CTError::new(DatabaseError, "Missing field in compound database"));
+            }
+            entries.push((normalize_name(data[0]), data[1].trim().to_string()));
+        }
+        Ok(CompoundDatabase { entries })
+    }
+
+    /// Finds the formula of the compound with the given name
+    pub fn lookup(&self, name: &str) -> Option<&str> {
+        let name = normalize_name(name);
+        self.entries.iter()
+                    .find(|&(n, _)| *n == name)
+                    .map(|(_, formula)| formula.as_str())
+    }
+
+    /// Replaces the input by the formula it names, if it is not already a formula
+    ///
+    /// Returns the (possibly) new input, along with each name which was replaced and its formula.
+    /// The element database decides which inputs are formulas.
+    pub fn resolve_molecule(&self, input: &str, elems: &mut ElemDatabase)
+                            -> (String, Vec<(String, String)>) {
+        match self.resolve_species(input, elems) {
+            Some((resolved, replaced)) => (resolved, vec!(replaced)),
+            None => (input.to_string(), Vec::new()),
+        }
+    }
+
+    /// Replaces the names of the compounds in a reaction by their formulas
+    ///
+    /// Only the species which are not already formulas are looked up. Returns the (possibly) new
+    /// input, along with each name which was replaced and its formula. If no names were replaced,
+    /// the input is returned untouched.
+    pub fn resolve_reaction(&self, input: &str, elems: &mut ElemDatabase)
+                            -> (String, Vec<(String, String)>) {
+        let mut replaced = Vec::new();
+        let mut sides = Vec::new();
+        for side in parser::split_sides(input).into_iter() {
+            let mut species = Vec::new();
+            for token in parser::split_species(side).into_iter() {
+                match self.resolve_species(token, elems) {
+                    Some((resolved, name)) => {
+                        species.push(resolved);
+                        replaced.push(name);
+                    },
+                    None => species.push(token.trim().to_string()),
+                }
+            }
+            sides.push(species.join(" + "));
+        }
+
+        if replaced.is_empty() {
+            (input.to_string(), replaced)
+        } else {
+            (sides.join(format!(" {} ", parser::arrow_text(input)).as_str()), replaced)
+        }
+    }
+
+    /// Replaces the name in a species by its formula, keeping its coefficient and state
+    ///
+    /// E.g. `2 water(l)` becomes `2 H2O(l)`. Returns the new species, along with the name which
+    /// was replaced and its formula, or None if the species is a formula or an unknown name.
+    fn resolve_species(&self, token: &str, elems: &mut ElemDatabase)
+                       -> Option<(String, (String, String))> {
+        let (rest, state) = parser::split_state(token.trim());
+        let rest = rest.trim();
+        let coef_len = rest.len() - rest.trim_start_matches(|ch: char| ch.is_ascii_digit()).len();
+        let (coef, name) = rest.split_at(coef_len);
+        let name = name.trim();
+        if is_formula(name, elems) {
+            return None;
+        }
+        // a name may itself start with digits, so it is first looked up as a whole
+        let (coef, name, formula) = match (self.lookup(rest), self.lookup(name)) {
+            (Some(formula), _) => ("", rest, formula),
+            (None, Some(formula)) => (coef, name, formula),
+            (None, None) => return None,
+        };
+        let coef = if coef.is_empty() { String::new() } else { format!("{} ", coef) };
+        Some((format!("{}{}{}", coef, formula, state), (name.to_string(), formula.to_string())))
+    }
+}
+
+/// Returns true if the input parses as a molecule, and every element in it is in the database
+///
+/// An input which does not parse is not a formula, so it is looked up by name, and is passed on
+/// untouched if that fails as well. If the database can not be read, the input is taken to be a
+/// formula, so in either case the command working on it reports the error.
+fn is_formula(input: &str, elems: &mut ElemDatabase) -> bool {
+    let mut parser = Parser::new(input);
+    match parser.parse_molecule() {
+        Ok(molecule) if parser.is_done() => {
+            let molecule = elem::strip_charge(elem::group_elems(molecule));
+            match elems.get_data(&molecule) {
+                Ok(_) => true,
+                Err(e) => e.kind != InputError,
+            }
+        },
+        _ => false,
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    let words: Vec<&str> = name.split(' ').filter(|w| !w.is_empty()).collect();
+    words.join(" ").to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static DUMMY_DB: &str = "\
+        water;H2O\n\
+        carbon dioxide;CO2\n\
+        glucose;C6H12O6\n\
+        oxygen;O2\n";
+
+    fn elems() -> ElemDatabase {
+        ElemDatabase::embedded()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|&(a, b)| (a.to_string(), b.to_string())).collect()
+    }
+
+    #[test]
+    fn lookup() {
+        let db = CompoundDatabase::from_csv(DUMMY_DB).unwrap();
+        assert_eq!(db.lookup("water"), Some("H2O"));
+        assert_eq!(db.lookup("  Carbon   Dioxide "), Some("CO2"));
+        assert_eq!(db.lookup("unobtainium"), None);
+    }
+
+    #[test]
+    fn molecule() {
+        let db = CompoundDatabase::from_csv(DUMMY_DB).unwrap();
+        let expected = ("H2O".to_string(), pairs(&[("water", "H2O")]));
+        assert_eq!(db.resolve_molecule("water", &mut elems()), expected);
+    }
+
+    #[test]
+    fn formula_is_kept() {
+        let db = CompoundDatabase::from_csv(DUMMY_DB).unwrap();
+        assert_eq!(db.resolve_molecule("H2O", &mut elems()), ("H2O".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn reaction() {
+        let db = CompoundDatabase::from_csv(DUMMY_DB).unwrap();
+        let result = db.resolve_reaction("glucose + oxygen -> carbon dioxide + H2O", &mut elems());
+        let expected = ("C6H12O6 + O2 -> CO2 + H2O".to_string(),
+                        pairs(&[("glucose", "C6H12O6"), ("oxygen", "O2"),
+                                ("carbon dioxide", "CO2")]));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn reaction_without_names() {
+        let db = CompoundDatabase::from_csv(DUMMY_DB).unwrap();
+        let input = "C3H8 +  O2 -> CO2 + H2O";
+        assert_eq!(db.resolve_reaction(input, &mut elems()), (input.to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn reaction_with_charges() {
+        let db = CompoundDatabase::from_csv(DUMMY_DB).unwrap();
+        let result = db.resolve_reaction("H^+ + OH^- → water", &mut elems());
+        let expected = ("H^+ + OH^- -> H2O".to_string(), pairs(&[("water", "H2O")]));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn capitalized_name() {
+        let db = CompoundDatabase::from_csv(DUMMY_DB).unwrap();
+        let expected = ("H2O".to_string(), pairs(&[("Water", "H2O")]));
+        assert_eq!(db.resolve_molecule("Water", &mut elems()), expected);
+        // a name which is also a formula stays a formula
+        let db = CompoundDatabase::from_csv("co;CO\n").unwrap();
+        assert_eq!(db.resolve_molecule("Co", &mut elems()), ("Co".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn element_database_decides() {
+        // without cobalt in the element database, `Co` can only be a name
+        let path = "compounds_test_elemdb.csv";
+        std::fs::write(path, "C;12.0107;Carbon;6\nO;15.9994;Oxygen;8\n").unwrap();
+        let mut elems = ElemDatabase::open(std::path::Path::new(path)).unwrap();
+        let db = CompoundDatabase::from_csv("co;CO\n").unwrap();
+        let result = db.resolve_molecule("Co", &mut elems);
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(result, ("CO".to_string(), pairs(&[("Co", "CO")])));
+    }
+
+    #[test]
+    fn coefficients_and_states() {
+        let db = CompoundDatabase::from_csv(DUMMY_DB).unwrap();
+        let result = db.resolve_reaction("2 water -> 2 H2(g) + oxygen(g)", &mut elems());
+        let expected = ("2 H2O -> 2 H2(g) + O2(g)".to_string(),
+                        pairs(&[("water", "H2O"), ("oxygen", "O2")]));
+        assert_eq!(result, expected);
+        let input = "Glucose(aq) + 6 Oxygen -> 6 carbon dioxide + 6 water(l)";
+        let result = db.resolve_reaction(input, &mut elems());
+        let expected = ("C6H12O6(aq) + 6 O2 -> 6 CO2 + 6 H2O(l)".to_string(),
+                        pairs(&[("Glucose", "C6H12O6"), ("Oxygen", "O2"),
+                                ("carbon dioxide", "CO2"), ("water", "H2O")]));
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn missing_field() {
+        assert!(CompoundDatabase::from_csv("water\n").is_err());
+    }
+}
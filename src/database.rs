@@ -1,44 +1,63 @@
-use std::old_io::File;
-use std::old_io::SeekStyle::SeekSet;
-use std::old_io::IoErrorKind::EndOfFile;
-use elem::{PerElem, Molecule};
-use error::{CTError, CTResult};
-use error::CTErrorKind::{InputError, DatabaseError};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Seek, SeekFrom};
+use std::path::Path;
+use crate::elem::{PerElem, Molecule};
+use crate::error::{CTError, CTResult};
+use crate::error::CTErrorKind::{InputError, DatabaseError};
 
 macro_rules! read_err (
-    () => (Err(CTError {
-        kind: DatabaseError,
-        desc: "Error reading the database".to_string(),
-        pos: None
-    }));
+    ($source:expr) => (
+        Err(CTError::new(DatabaseError, "Error reading the database").with_source($source))
+    );
 );
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ElemData {
+    #[cfg_attr(feature = "serde", serde(rename = "symbol"))]
     pub short_name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "name"))]
     pub long_name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "molar_mass"))]
     pub mass: f64,
+    #[cfg_attr(feature = "serde", serde(rename = "atomic_number"))]
     pub atomic_num: u16,
 }
 
+/// The element table of `elemdb.csv`, built into the library
+const EMBEDDED_DB: &str = include_str!("../elemdb.csv");
+
+/// Anything the database can be read from, i.e. a file or the embedded table
+trait Source: BufRead + Seek {}
+
+impl<T: BufRead + Seek> Source for T {}
+
 pub struct ElemDatabase {
-    db: File,
+    db: Box<dyn Source>,
 }
 
 impl ElemDatabase {
     /// Try to make the database with the file at the given oath
     pub fn open(path: &Path) -> CTResult<ElemDatabase> {
         match File::open(path) {
-            Ok(db_file) => Ok(ElemDatabase { db: db_file }),
-            Err(_) => Err(CTError {
-                kind: DatabaseError,
-                desc: format!("Could not open database file. Expected at: {:?}",
-                              path.as_str().unwrap_or("same directory as the program")),
-                pos: None,
-            }),
+            Ok(db_file) => Ok(ElemDatabase { db: Box::new(BufReader::new(db_file)) }),
+            Err(e) => {
+                let desc = format!("Could not open database file. Expected at: {:?}",
+                                   path.to_str().unwrap_or("same directory as the program"));
+                Err(CTError::new(DatabaseError, desc).with_source(e))
+            },
         }
     }
 
+    /// Makes the database from the element table built into the library
+    ///
+    /// The table is the `elemdb.csv` shipped with the program, so no file has to be read, e.g.
+    /// when running in a web page.
+    pub fn embedded() -> ElemDatabase {
+        ElemDatabase { db: Box::new(Cursor::new(EMBEDDED_DB.as_bytes())) }
+    }
+
     /// Try to get the data matching the given PerElem.
     ///
     /// This function errors if the PerElem could not be found, or the database
@@ -51,7 +70,7 @@ impl ElemDatabase {
         } else {
             // but in case they weren't, we return to the beginning of the underlying file, since
             // the data might lie on a line we have previously read past
-            self.db.seek(0, SeekSet).ok().expect("Internal error reading database");
+            self.db.seek(SeekFrom::Start(0)).expect("Internal error reading database");
             self.do_data_search(elem)
         }
     }
@@ -59,23 +78,50 @@ impl ElemDatabase {
     /// Try to get the data for all the provided PerElems.
     ///
     /// This function errors if one of the PerElem could not be found, or the
-    /// database could not be read.
+    /// database could not be read. Every PerElem which could not be found is reported in the
+    /// error, not just the first one.
     pub fn get_data(&mut self, elems: &Molecule) -> CTResult<Vec<ElemData>> {
         let mut out = Vec::new();
+        let mut unknown = Vec::new();
         for elem in elems.iter() {
             match self.get_single_data(elem) {
                 Ok(data) => out.push(data),
-                Err(e) => return Err(e),
+                Err(e) => {
+                    if e.kind != InputError {
+                        return Err(e);
+                    }
+                    unknown.push(e);
+                },
             }
         }
-        Ok(out)
+        if unknown.is_empty() {
+            Ok(out)
+        } else {
+            Err(CTError::combine(unknown))
+        }
+    }
+
+    /// Reads the data for every element in the database, in the order they are stored
+    pub fn all_data(&mut self) -> CTResult<Vec<ElemData>> {
+        let mut contents = String::new();
+        if let Err(e) = self.db.seek(SeekFrom::Start(0))
+                               .and_then(|_| self.db.read_to_string(&mut contents)) {
+            return read_err!(e);
+        }
+        // go back to the beginning, so later searches start from the first element
+        self.db.seek(SeekFrom::Start(0)).expect("Internal error reading database");
+        contents.lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(decode_line)
+                .collect()
     }
 
     fn do_data_search(&mut self, elem: &PerElem) -> CTResult<ElemData> {
         loop {
             // TODO: make it so this function returns the 'not found' error
-            let line = try!(self.read_line(elem));
-            if line.starts_with(elem.name.as_slice()) {
+            let line = self.read_line(elem)?;
+            // the symbol must match the first field exactly, or e.g. L would be found as La
+            if line.trim().split(';').next() == Some(elem.name.as_str()) {
                 return decode_line(&line);
             }
         }
@@ -84,47 +130,41 @@ impl ElemDatabase {
     fn read_line(&mut self, elem: &PerElem) -> CTResult<String> {
         // we know that no line in the database is more than 30 characters long
         let mut buf = Vec::with_capacity(30);
-        loop {
-            match self.db.read_byte() {
-                Ok(b) if b == b'\n' => break,
-                Ok(b) => buf.push(b),
-                Err(ref e) if e.kind == EndOfFile => return Err(CTError {
-                    kind: InputError,
-                    desc: format!("Could not find element: {:?}", elem.name),
-                    pos: Some((elem.pos, elem.len)),
-                }),
-                Err(_) => return read_err!()
-            }
+        match self.db.read_until(b'\n', &mut buf) {
+            Ok(0) => {
+                return Err(CTError::new(InputError,
+                                        format!("Could not find element: {:?}", elem.name))
+                    .with_pos((elem.pos, elem.len)))
+            },
+            Ok(_) => (),
+            Err(e) => return read_err!(e),
+        }
+        if buf.last() == Some(&b'\n') {
+            buf.pop();
+        }
+        match String::from_utf8(buf) {
+            Ok(line) => Ok(line),
+            Err(e) => read_err!(e),
         }
-        String::from_utf8(buf).or_else(|_| read_err!())
     }
 }
 
-fn decode_line(line: &String) -> CTResult<ElemData> {
+fn decode_line(line: &str) -> CTResult<ElemData> {
     let data: Vec<&str> = line.trim().split(';').collect();
     if data.len() < 4 {
-        Err(CTError {
-            kind: DatabaseError,
-            desc: "Missing field in database".to_string(),
-            pos: None
-        })
+        Err(CTError::new(DatabaseError, "Missing field in database"))
     } else {
-        let mass = data[1].parse::<f64>();
-        let atomic_num = data[3].parse::<u16>();
-        if let (Ok(m), Ok(an)) = (mass, atomic_num) {
-            Ok(ElemData {
-                short_name: data[0].to_string(),
-                long_name: data[2].to_string(),
-                mass: m,
-                atomic_num: an,
-            })
-        } else {
-            Err(CTError {
-                kind: DatabaseError,
-                desc: "Field in database corrupted".to_string(),
-                pos: None,
-            })
-        }
+        let corrupted = |source: Box<dyn Error + Send + Sync>| {
+            CTError::new(DatabaseError, "Field in database corrupted").with_source(source)
+        };
+        let mass = data[1].parse::<f64>().map_err(|e| corrupted(Box::new(e)))?;
+        let atomic_num = data[3].parse::<u16>().map_err(|e| corrupted(Box::new(e)))?;
+        Ok(ElemData {
+            short_name: data[0].to_string(),
+            long_name: data[2].to_string(),
+            mass,
+            atomic_num,
+        })
     }
 }
 
@@ -132,22 +172,22 @@ fn decode_line(line: &String) -> CTResult<ElemData> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::old_io::File;
-    use std::old_io::fs;
-    use elem::PerElem;
+    use std::fs;
+    use std::path::Path;
+    use crate::elem::PerElem;
 
     fn make_dummy_db(name: &str, contents: &str) -> ElemDatabase {
-        if let Err(e) = File::create(&Path::new(name)).and_then(|mut f| f.write_str(contents)) {
+        if let Err(e) = fs::write(name, contents) {
             // if we can't make the database we can't test, so just abort here
-            panic!("Could not create dummy database: {:?}", e.desc);
+            panic!("Could not create dummy database: {}", e);
         }
-        ElemDatabase::open(&Path::new(name)).unwrap()
+        ElemDatabase::open(Path::new(name)).unwrap()
     }
 
     fn remove_dummy_db(name: &str) {
-        if let Err(e) = fs::unlink(&Path::new(name)) {
+        if let Err(e) = fs::remove_file(name) {
             // if we can't remove the database something is wrong, and we abort the test
-            panic!("Could not remove dummy database: {:?}", e.desc);
+            panic!("Could not remove dummy database: {}", e);
         }
     }
 
@@ -210,6 +250,60 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn prefix_of_symbol() {
+        let db_name = "prefix_of_symbol_db";
+        let mut db = make_dummy_db(db_name,
+            "La;138.90547;Lanthanum;57\n\
+            Xe;131.293;Xenon;54\n");
+        let mut lookup = |name: &str| {
+            db.get_single_data(&PerElem { name: name.to_string(), coef: 1, pos: 0, len: 1 })
+        };
+        let (l, x) = (lookup("L"), lookup("X"));
+        remove_dummy_db(db_name);
+        assert_eq!(l.unwrap_err().desc, "Could not find element: \"L\"");
+        assert_eq!(x.unwrap_err().desc, "Could not find element: \"X\"");
+    }
+
+    #[test]
+    fn missing_elems() {
+        let db_name = "missing_elems_db";
+        let mut db = make_dummy_db(db_name, "A;123.456789;Abba;12\n");
+        let result = db.get_data(&vec!(
+            PerElem { name: "B".to_string(), coef: 1, pos: 0, len: 1 },
+            PerElem { name: "A".to_string(), coef: 1, pos: 1, len: 1 },
+            PerElem { name: "C".to_string(), coef: 1, pos: 2, len: 1 }
+        ));
+        remove_dummy_db(db_name);
+        let err = result.err().unwrap();
+        let positions: Vec<(usize, usize)> = err.labels.iter().map(|l| l.pos).collect();
+        assert_eq!(positions, vec!((0, 1), (2, 1)));
+    }
+
+    #[test]
+    fn all_elems() {
+        let db_name = "all_elems_db";
+        let mut db = make_dummy_db(db_name,
+            "A;1;Abba;2\n\
+            B;3;Beta;4\n");
+        let names: Vec<String> = db.all_data().unwrap().into_iter().map(|d| d.long_name).collect();
+        remove_dummy_db(db_name);
+        assert_eq!(names, vec!("Abba".to_string(), "Beta".to_string()));
+    }
+
+    #[test]
+    fn embedded_elems() {
+        let mut db = ElemDatabase::embedded();
+        let data = db.get_data(&vec!(
+            PerElem { name: "Fe".to_string(), coef: 1, pos: 0, len: 2 },
+            PerElem { name: "C".to_string(), coef: 1, pos: 2, len: 1 }
+        )).unwrap();
+        let names: Vec<&str> = data.iter().map(|d| d.long_name.as_str()).collect();
+        assert_eq!(names, vec!("Iron", "Carbon"));
+        assert_eq!(db.all_data().unwrap().len(), ElemDatabase::open(Path::new("elemdb.csv"))
+                                                      .unwrap().all_data().unwrap().len());
+    }
+
     #[test]
     fn missing_field() {
         let db_name = "missing_field_db";
@@ -231,4 +325,30 @@ mod test {
         remove_dummy_db(db_name);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn corrupted_field_source() {
+        let db_name = "corrupted_field_source_db";
+        let mut db = make_dummy_db(db_name, "A;1.0;Abba;twelve\n");
+        let result = db.get_single_data(
+            &PerElem { name: "A".to_string(), coef: 1, pos: 0, len: 2 }
+        );
+        remove_dummy_db(db_name);
+        let source = result.unwrap_err().source.expect("corrupted field should have a source");
+        assert_eq!(source.to_string(), "invalid digit found in string");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let data = ElemData {
+            short_name: "Fe".to_string(),
+            long_name: "Iron".to_string(),
+            mass: 55.845,
+            atomic_num: 26,
+        };
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(json, r#"{"symbol":"Fe","name":"Iron","molar_mass":55.845,"atomic_number":26}"#);
+        assert_eq!(serde_json::from_str::<ElemData>(&json).unwrap(), data);
+    }
 }
\ No newline at end of file
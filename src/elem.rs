@@ -1,8 +1,13 @@
 use std::fmt::{Display, Formatter, Error};
+use crate::balance;
+use crate::error::CTResult;
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PerElem {
+    #[cfg_attr(feature = "serde", serde(rename = "symbol"))]
     pub name: String,
+    #[cfg_attr(feature = "serde", serde(rename = "count"))]
     pub coef: u32,
     pub pos: usize,
     pub len: usize,
@@ -10,20 +15,161 @@ pub struct PerElem {
 
 pub type Molecule = Vec<PerElem>;
 
+/// Writes a molecule as a formula, e.g. `H2O` or `SO4^2-`, when used with `{}`
+///
+/// Since a Molecule is just a Vec, it can not implement Display itself.
+pub struct Formula<'a>(pub &'a Molecule);
+
+impl PerElem {
+    /// Returns true if this is the charge of a molecule, rather than a periodic element
+    ///
+    /// A charge is stored as a PerElem named `+` or `-`, with the size of the charge as the coef.
+    pub fn is_charge(&self) -> bool {
+        self.name == "+" || self.name == "-"
+    }
+}
+
+/// The physical state of a molecule, as written after it in a reaction, e.g. `H2O(l)`
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum State {
+    Solid,
+    Liquid,
+    Gas,
+    Aqueous,
+}
+
+/// A molecule taking part in a reaction, along with how it was written in the reaction
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Species {
+    pub molecule: Molecule,
+    /// The stoichiometric coefficient, which is 1 unless it was written before the molecule or
+    /// found by balancing the reaction
+    #[cfg_attr(feature = "serde", serde(rename = "coefficient"))]
+    pub coef: u32,
+    pub state: Option<State>,
+    /// The position and length of the species in the input, including its coefficient and state
+    pub pos: (usize, usize),
+}
+
+/// The kind of arrow separating the two sides of a reaction
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Arrow {
+    /// A reaction which goes to completion, written `->` or `→`
+    Forward,
+    /// A reaction in equilibrium, written `<=>`, `<->` or `⇌`
+    Equilibrium,
+}
+
+/// A chemical reaction, e.g. `2 H2(g) + O2(g) -> 2 H2O(l)`
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reaction {
+    pub reactants: Vec<Species>,
+    pub products: Vec<Species>,
+    pub arrow: Arrow,
+    /// The conditions written after the arrow, e.g. `heat` in `CaCO3 ->[heat] CaO + CO2`
+    pub conditions: Vec<String>,
+}
+
+impl Species {
+    /// Makes a species of the molecule with a coefficient of 1 and no state
+    pub fn new(molecule: Molecule) -> Species {
+        let pos = match (molecule.first(), molecule.last()) {
+            (Some(first), Some(last)) => (first.pos, last.pos + last.len - first.pos),
+            _ => (0, 0),
+        };
+        Species { molecule, coef: 1, state: None, pos }
+    }
+
+    /// Finds the charge of the molecule, which is negative for a negative charge
+    pub fn charge(&self) -> i32 {
+        self.molecule.iter().fold(0, |total, e| match e.name.as_str() {
+            "+" => total + e.coef as i32,
+            "-" => total - e.coef as i32,
+            _ => total,
+        })
+    }
+}
+
+impl Reaction {
+    /// Makes a forward reaction without conditions, where every species has a coefficient of 1
+    pub fn new(reactants: Vec<Molecule>, products: Vec<Molecule>) -> Reaction {
+        Reaction {
+            reactants: reactants.into_iter().map(Species::new).collect(),
+            products: products.into_iter().map(Species::new).collect(),
+            arrow: Arrow::Forward,
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Iterates over every species in the reaction, with the reactants first
+    pub fn species(&self) -> impl Iterator<Item = &Species> {
+        self.reactants.iter().chain(self.products.iter())
+    }
+
+    /// Returns the coefficient of every species, in the same order as `species`
+    pub fn coefs(&self) -> Vec<u32> {
+        self.species().map(|s| s.coef).collect()
+    }
+
+    /// Sets the coefficient of every species, in the same order as `species`
+    pub fn set_coefs(&mut self, coefs: &[u32]) {
+        let species = self.reactants.iter_mut().chain(self.products.iter_mut());
+        for (species, &coef) in species.zip(coefs.iter()) {
+            species.coef = coef;
+        }
+    }
+
+    /// Balances the reaction, replacing the coefficients of the species by the balanced ones
+    pub fn balance(&mut self) -> CTResult<()> {
+        let coefs = balance::balance_reaction(self)?;
+        self.set_coefs(&coefs);
+        Ok(())
+    }
+
+    /// Checks that the coefficients of the species balance the reaction
+    ///
+    /// The error names every element which is not balanced, and points at the species with it.
+    pub fn check(&self) -> CTResult<()> {
+        balance::check_balance(self)
+    }
+
+    /// Swaps the reactants and the products, keeping the coefficients
+    pub fn reverse(&mut self) {
+        std::mem::swap(&mut self.reactants, &mut self.products);
+    }
+
+    /// Writes the arrow along with the conditions of the reaction, e.g. `->[heat]`
+    pub fn arrow_text(&self) -> String {
+        if self.conditions.is_empty() {
+            format!("{}", self.arrow)
+        } else {
+            format!("{}[{}]", self.arrow, self.conditions.join(", "))
+        }
+    }
+}
+
 /// Sorts the PerElems and groups those with the same name field.
 ///
 /// Grouping of two (or more) PerElems means adding the coef field of the
 /// duplicate to the one already found, and then throwing away the duplicate.
-/// E.g. CH3CH3 would turn into C2H6.
+/// E.g. CH3CH3 would turn into C2H6. A total which does not fit in a coefficient is capped, but
+/// the parser never makes a molecule with such a total.
 pub fn group_elems(mut molecule: Molecule) -> Molecule {
     let mut out = Vec::<PerElem>::new();
-    molecule.as_mut_slice().sort_by(|a, b| a.name.cmp(&b.name));
+    molecule.sort_by(|a, b| a.name.cmp(&b.name));
     // since the elements are now sorted, if the current elem does not match the
     // last element in out (i.e. what we previously pushed), then it won't match
     // anything in out
     for elem in molecule.into_iter() {
-        if out.last().and_then(|e| Some(e.name == elem.name)).unwrap_or(false) {
-            out.last_mut().unwrap().coef += elem.coef;
+        if out.last().map(|e| e.name == elem.name).unwrap_or(false) {
+            let last = out.last_mut().unwrap();
+            last.coef = last.coef.saturating_add(elem.coef);
         } else {
             out.push(elem);
         }
@@ -31,19 +177,81 @@ pub fn group_elems(mut molecule: Molecule) -> Molecule {
     out
 }
 
-impl Display for Molecule {
+/// Removes the charge from a molecule, so only the periodic elements are left
+///
+/// This is needed before looking up the elements in the database, since the charge is not an
+/// element. The mass of the electrons making up the charge is small enough to be ignored.
+pub fn strip_charge(molecule: Molecule) -> Molecule {
+    molecule.into_iter().filter(|e| !e.is_charge()).collect()
+}
+
+impl Display for Formula<'_> {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
-        for elem in self.iter() {
-            try!(fmt.write_str(elem.name.as_slice()));
-            // TODO: Use a proper conversion function -- if it exists
+        for elem in self.0.iter().filter(|e| !e.is_charge()) {
+            fmt.write_str(&elem.name)?;
             if elem.coef > 1 {
-                try!(fmt.write_str(format!("{}", elem.coef).as_slice()));
+                write!(fmt, "{}", elem.coef)?;
             }
         }
+        // the charge is always written last, no matter where it is in the molecule
+        for charge in self.0.iter().filter(|e| e.is_charge()) {
+            fmt.write_str("^")?;
+            if charge.coef > 1 {
+                write!(fmt, "{}", charge.coef)?;
+            }
+            fmt.write_str(&charge.name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Species {
+    /// Writes the molecule along with its state, e.g. `H2O(l)`, but without the coefficient
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        write!(fmt, "{}", Formula(&self.molecule))?;
+        if let Some(state) = self.state {
+            write!(fmt, "({})", state)?;
+        }
         Ok(())
     }
 }
 
+impl Display for Reaction {
+    /// Writes the reaction the way it would be typed, e.g. `2 H2(g) + O2(g) -> 2 H2O(l)`
+    ///
+    /// Coefficients of 1 are left out.
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        let side = |species: &[Species]| {
+            let names: Vec<String> = species.iter().map(|s| match s.coef {
+                1 => format!("{}", s),
+                coef => format!("{} {}", coef, s),
+            }).collect();
+            names.join(" + ")
+        };
+        write!(fmt, "{} {} {}", side(&self.reactants), self.arrow_text(), side(&self.products))
+    }
+}
+
+impl Display for Arrow {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        fmt.write_str(match *self {
+            Arrow::Forward => "->",
+            Arrow::Equilibrium => "<=>",
+        })
+    }
+}
+
+impl Display for State {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        fmt.write_str(match *self {
+            State::Solid => "s",
+            State::Liquid => "l",
+            State::Gas => "g",
+            State::Aqueous => "aq",
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -67,7 +275,7 @@ mod test {
     #[test]
     fn molecule_display() {
         let molecule = vec!(dummy_elem!("C", 2), dummy_elem!("H", 3));
-        let result = format!("{}", molecule);
+        let result = format!("{}", Formula(&molecule));
         let expected = "C2H3";
         assert_eq!(result, expected);
     }
@@ -75,8 +283,76 @@ mod test {
     #[test]
     fn molecule_display_coefs() {
        let molecule = vec!(dummy_elem!("C", 1), dummy_elem!("H", 3));
-        let result = format!("{}", molecule);
+        let result = format!("{}", Formula(&molecule));
         let expected = "CH3";
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn molecule_display_charge() {
+        let molecule = vec!(dummy_elem!("-", 2), dummy_elem!("O", 4), dummy_elem!("S", 1));
+        let result = format!("{}", Formula(&molecule));
+        let expected = "O4S^2-";
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn species_display() {
+        let mut species = Species::new(vec!(dummy_elem!("Na")));
+        species.state = Some(State::Aqueous);
+        assert_eq!(format!("{}", species), "Na(aq)");
+    }
+
+    #[test]
+    fn species_charge() {
+        let molecule = vec!(dummy_elem!("O", 4), dummy_elem!("S"), dummy_elem!("-", 2));
+        let species = Species::new(molecule);
+        assert_eq!(species.charge(), -2);
+    }
+
+    #[test]
+    fn reaction_display() {
+        let mut reaction = Reaction::new(vec!(vec!(dummy_elem!("H", 2)), vec!(dummy_elem!("O", 2))),
+                                         vec!(vec!(dummy_elem!("H", 2), dummy_elem!("O"))));
+        reaction.set_coefs(&[2, 1, 2]);
+        assert_eq!(format!("{}", reaction), "2 H2 + O2 -> 2 H2O");
+        reaction.arrow = Arrow::Equilibrium;
+        reaction.conditions = vec!("Pt".to_string(), "heat".to_string());
+        assert_eq!(format!("{}", reaction), "2 H2 + O2 <=>[Pt, heat] 2 H2O");
+    }
+
+    #[test]
+    fn reverse() {
+        let mut reaction = Reaction::new(vec!(vec!(dummy_elem!("C"))),
+                                         vec!(vec!(dummy_elem!("H"))));
+        reaction.set_coefs(&[1, 2]);
+        reaction.reverse();
+        assert_eq!(format!("{}", reaction), "2 H -> C");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_field_names() {
+        let json = serde_json::to_string(&dummy_elem!("O", 2)).unwrap();
+        assert_eq!(json, r#"{"symbol":"O","count":2,"pos":0,"len":1}"#);
+        assert_eq!(serde_json::to_string(&State::Aqueous).unwrap(), r#""aqueous""#);
+        assert_eq!(serde_json::to_string(&Arrow::Equilibrium).unwrap(), r#""equilibrium""#);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut reaction = crate::parser::Parser::new("N2(g) + H2(g) <=>[Fe] NH3(g)")
+                               .parse_reaction()
+                               .unwrap();
+        reaction.balance().unwrap();
+        let json = serde_json::to_string(&reaction).unwrap();
+        assert_eq!(serde_json::from_str::<Reaction>(&json).unwrap(), reaction);
+    }
+
+    #[test]
+    fn strip() {
+        let molecule = vec!(dummy_elem!("Na"), dummy_elem!("+"));
+        assert_eq!(strip_charge(molecule), vec!(dummy_elem!("Na")));
+    }
 }
\ No newline at end of file
@@ -1,11 +1,37 @@
-#[derive(Debug, PartialEq)]
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::{IsTerminal, Write};
+use crate::json::Json;
+
+const RED: &str = "\x1b[31m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug)]
 pub struct CTError {
     pub kind: CTErrorKind,
     pub desc: String,
     pub pos: Option<(usize, usize)>,
+    /// Extra positions to point at, each with a note explaining it. If there are any, these are
+    /// shown instead of `pos`.
+    pub labels: Vec<Label>,
+    /// A hint on how to fix the error
+    pub help: Option<String>,
+    /// The lower level error which caused this one, e.g. a failed read or a malformed number
+    pub source: Option<Box<dyn Error + Send + Sync>>,
 }
 
-#[derive(Debug, PartialEq)]
+/// A position in the input, along with what is wrong or interesting there
+#[derive(Debug, PartialEq, Clone)]
+pub struct Label {
+    pub pos: (usize, usize),
+    pub text: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum CTErrorKind {
     InputError,
     DatabaseError,
@@ -14,32 +40,332 @@ pub enum CTErrorKind {
 
 pub type CTResult<T> = Result<T, CTError>;
 
+impl PartialEq for CTError {
+    fn eq(&self, other: &CTError) -> bool {
+        // the sources can not be compared directly, so we settle for comparing their messages
+        let source = |e: &CTError| e.source.as_ref().map(|s| s.to_string());
+        self.kind == other.kind && self.desc == other.desc && self.pos == other.pos &&
+            self.labels == other.labels && self.help == other.help && source(self) == source(other)
+    }
+}
+
+impl fmt::Display for CTError {
+    /// Writes the description of the error
+    ///
+    /// Use `message` or `write_to` for the full diagnostic, which points at the error in the
+    /// input.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.desc.as_str())
+    }
+}
+
+impl Error for CTError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|e| e.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+impl CTErrorKind {
+    /// The status the program exits with when it fails with this kind of error
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            CTErrorKind::InputError => 1,
+            CTErrorKind::UsageError => 2,
+            CTErrorKind::DatabaseError => 3,
+        }
+    }
+}
+
 impl CTError {
-    /// Pretty-prints the CTError struct to stdout
-    pub fn print(&self, extra_desc: Option<&String>) {
-        println!("{}", self.desc);
+    /// Makes an error of the given kind, which does not point at the input
+    ///
+    /// The position, labels, help and source are added with the `with_*` methods, e.g.
+    /// `CTError::new(InputError, "Missing element").with_pos((3, 1))`.
+    pub fn new<S: Into<String>>(kind: CTErrorKind, desc: S) -> CTError {
+        CTError {
+            kind,
+            desc: desc.into(),
+            pos: None,
+            labels: Vec::new(),
+            help: None,
+            source: None,
+        }
+    }
+
+    /// Points the error at the given position and length in the input
+    pub fn with_pos(self, pos: (usize, usize)) -> CTError {
+        CTError { pos: Some(pos), ..self }
+    }
+
+    /// Adds a label, pointing at another part of the input with a note explaining it
+    pub fn with_label<S: Into<String>>(mut self, pos: (usize, usize), text: S) -> CTError {
+        self.labels.push(Label { pos, text: text.into() });
+        self
+    }
+
+    /// Adds several labels at once
+    pub fn with_labels(mut self, labels: Vec<Label>) -> CTError {
+        self.labels.extend(labels);
+        self
+    }
+
+    /// Adds a hint on how to fix the error
+    pub fn with_help<S: Into<String>>(self, help: S) -> CTError {
+        CTError { help: Some(help.into()), ..self }
+    }
+
+    /// Adds the lower level error which caused this one
+    pub fn with_source<E: Into<Box<dyn Error + Send + Sync>>>(self, source: E) -> CTError {
+        CTError { source: Some(source.into()), ..self }
+    }
+
+    /// Makes a JSON object describing the error
+    ///
+    /// The object has a single field `error`, holding the kind, description, position, labels and
+    /// help of the error. Positions are either null or an array holding the position and length.
+    /// The labels are an array of objects with a `pos` and a `text`, and the help is either null
+    /// or a string.
+    pub fn to_json(&self) -> Json {
+        let span = |(pos, len): (usize, usize)| {
+            Json::Array(vec!(Json::Number(pos as f64), Json::Number(len as f64)))
+        };
+        let labels = self.labels.iter().map(|label| {
+            Json::object(vec!(
+                ("pos", span(label.pos)),
+                ("text", Json::string(&label.text)),
+            ))
+        }).collect();
+        Json::object(vec!(
+            ("error", Json::object(vec!(
+                ("kind", Json::string(format!("{:?}", self.kind))),
+                ("desc", Json::string(&self.desc)),
+                ("pos", self.pos.map_or(Json::Null, span)),
+                ("labels", Json::Array(labels)),
+                ("help", self.help.as_ref().map_or(Json::Null, Json::string)),
+            ))),
+        ))
+    }
+
+    /// Combines several errors in the same input into one, which points at each of them
+    ///
+    /// A single error is returned as it is. Otherwise each error becomes a label with its
+    /// description, and the help of every error is kept. An error without a position has nothing
+    /// to label, so its description is kept with the help instead. The kind and position are those
+    /// of the first error. There must be at least one error.
+    pub fn combine(mut errors: Vec<CTError>) -> CTError {
+        if errors.len() == 1 {
+            return errors.pop().unwrap();
+        }
+        let helps: Vec<&str> = errors.iter().flat_map(|e| {
+            let unlabeled = if e.pos.is_none() { Some(e.desc.as_str()) } else { None };
+            unlabeled.into_iter().chain(e.help.as_deref())
+        }).collect();
+        let help = if helps.is_empty() { None } else { Some(helps.join("; ")) };
+        let labels = errors.iter().filter_map(|e| {
+            e.pos.map(|pos| Label { pos, text: e.desc.clone() })
+        }).collect();
+        CTError {
+            pos: errors[0].pos,
+            help,
+            ..CTError::new(errors[0].kind, format!("Found {} errors", errors.len()))
+                .with_labels(labels)
+        }
+    }
+
+    /// Moves the position of the error, for when the erroneous input was part of a longer line
+    pub fn shift(self, offset: usize) -> CTError {
+        let labels = self.labels.into_iter().map(|label| {
+            Label { pos: (label.pos.0 + offset, label.pos.1), ..label }
+        }).collect();
+        CTError { pos: self.pos.map(|(pos, len)| (pos + offset, len)), labels, ..self }
+    }
+
+    /// Pretty-prints the CTError struct to stderr
+    ///
+    /// The message is coloured when stderr is a terminal, unless the NO_COLOR environment
+    /// variable is set.
+    pub fn print(&self, extra_desc: Option<&str>) {
+        let color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+        // there is nowhere left to report the error if stderr can not be written to
+        let _ = self.write_to(&mut io::stderr(), extra_desc, color);
+    }
+
+    /// Writes the error in the same way as `print`, but to any writer
+    pub fn write_to<W: Write>(&self, out: &mut W, extra_desc: Option<&str>, color: bool)
+                              -> io::Result<()> {
+        out.write_all(self.message(extra_desc, color).as_bytes())
+    }
+
+    /// Formats the error in the same way as `print` writes it
+    ///
+    /// For input errors the extra description is the input, which the error is pointed out in.
+    /// For usage errors it is the usage text, which is written after the error.
+    pub fn message(&self, extra_desc: Option<&str>, color: bool) -> String {
+        let paint = |text: &str, code: &str| {
+            if color { format!("{}{}{}", code, text, RESET) } else { text.to_string() }
+        };
+        let mut out = format!("{}\n", paint(self.desc.as_str(), BOLD_RED));
         // some errors will have extra stuff to report to make the message clearer for the user
         match self.kind {
             CTErrorKind::InputError => {
-                if let (Some((pos, len)), Some(input)) = (self.pos, extra_desc) {
-                    println!("    {}", input);
-                    print!("    ");
-                    for _ in 0..pos {
-                        print!(" ");
+                if let Some(input) = extra_desc {
+                    // without any labels, the position of the error is pointed at on its own
+                    let labels: Vec<((usize, usize), &str)> = if self.labels.is_empty() {
+                        self.pos.iter().map(|&pos| (pos, "")).collect()
+                    } else {
+                        self.labels.iter().map(|l| (l.pos, l.text.as_str())).collect()
+                    };
+                    if !labels.is_empty() {
+                        out.push_str(format!("    {}\n", input).as_str());
                     }
-                    print!("^");
-                    for _ in 1..len {
-                        print!("~");
+                    for &(pos, text) in labels.iter() {
+                        out.push_str("    ");
+                        out.push_str(paint(underline(input, pos).as_str(), RED).as_str());
+                        if !text.is_empty() {
+                            out.push(' ');
+                            out.push_str(text);
+                        }
+                        out.push('\n');
                     }
-                    println!("");
                 }
             },
             CTErrorKind::UsageError => {
                 if let Some(usage) = extra_desc {
-                    println!("{}", usage);
+                    out.push_str(format!("{}\n", usage).as_str());
                 }
             }
             _ => (),
         }
+        if let Some(ref help) = self.help {
+            out.push_str(format!("{} {}\n", paint("help:", CYAN), help).as_str());
+        }
+        out
+    }
+}
+
+/// Makes the line of spaces and carets which goes below the input to point at a position in it
+fn underline(input: &str, (pos, len): (usize, usize)) -> String {
+    // the position is a byte offset, but the caret must be placed below the right character,
+    // even if some of the characters before it are multi-byte
+    let column = input.char_indices().take_while(|&(i, _)| i < pos).count();
+    let width = input.char_indices()
+                     .skip_while(|&(i, _)| i < pos)
+                     .take_while(|&(i, _)| i < pos + len)
+                     .count();
+    let mut out = String::new();
+    for _ in 0..column {
+        out.push(' ');
+    }
+    out.push('^');
+    for _ in 1..width {
+        out.push('~');
     }
-}
\ No newline at end of file
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::CTErrorKind::{InputError, DatabaseError, UsageError};
+
+    fn bad_number() -> CTError {
+        let source = "1.0x".parse::<f64>().unwrap_err();
+        CTError::new(DatabaseError, "Field in database corrupted").with_source(source)
+    }
+
+    #[test]
+    fn display() {
+        let err = CTError::new(InputError, "Unexpected character").with_pos((2, 1))
+                                                                  .with_help("remove it");
+        assert_eq!(err.to_string(), "Unexpected character");
+    }
+
+    #[test]
+    fn source_chain() {
+        let err = bad_number();
+        assert_eq!(err.source().map(|e| e.to_string()),
+                   Some("invalid float literal".to_string()));
+
+        let io_err = io::Error::new(io::ErrorKind::NotFound, "no such file");
+        let inner = CTError::new(DatabaseError, "Error reading the database").with_source(io_err);
+        let outer = CTError::new(InputError, "Could not look up the element").with_source(inner);
+        let mut chain = Vec::new();
+        let mut cause = outer.source();
+        while let Some(e) = cause {
+            chain.push(e.to_string());
+            cause = e.source();
+        }
+        assert_eq!(chain, vec!("Error reading the database", "no such file"));
+        assert!(CTError::new(UsageError, "Too many arguments.").source().is_none());
+    }
+
+    #[test]
+    fn input_message() {
+        let err = CTError::new(InputError, "Unexpected character").with_pos((2, 1))
+                                                                  .with_help("remove it");
+        assert_eq!(err.message(Some("H2!O"), false),
+                   "Unexpected character\n    H2!O\n      ^\nhelp: remove it\n");
+        assert_eq!(err.message(Some("H2!O"), true),
+                   "\x1b[1;31mUnexpected character\x1b[0m\n    H2!O\n    \x1b[31m  ^\x1b[0m\n\
+                    \x1b[36mhelp:\x1b[0m remove it\n");
+    }
+
+    #[test]
+    fn labelled_message() {
+        let err = CTError::new(InputError, "Missing closing parentheses")
+            .with_pos((4, 1))
+            .with_label((0, 1), "opened here")
+            .with_label((4, 1), "expected closing parenthesis here");
+        assert_eq!(err.message(Some("(OH2"), false),
+                   "Missing closing parentheses\n    (OH2\n    ^ opened here\n        ^ expected \
+                    closing parenthesis here\n");
+    }
+
+    #[test]
+    fn usage_message() {
+        let err = CTError::new(UsageError, "Missing command.");
+        assert_eq!(err.message(Some("Usage: chemtool <command>"), false),
+                   "Missing command.\nUsage: chemtool <command>\n");
+        assert_eq!(bad_number().message(None, false), "Field in database corrupted\n");
+    }
+
+    #[test]
+    fn write_to() {
+        let err = CTError::new(InputError, "Unexpected character").with_pos((2, 1));
+        let mut out = Vec::new();
+        err.write_to(&mut out, Some("H2!O"), false).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), err.message(Some("H2!O"), false));
+    }
+
+    #[test]
+    fn combine() {
+        let single = CTError::new(InputError, "Unexpected character").with_pos((2, 1));
+        assert_eq!(CTError::combine(vec!(single)),
+                   CTError::new(InputError, "Unexpected character").with_pos((2, 1)));
+
+        let errors = vec!(
+            CTError::new(InputError, "Could not find element: \"Xx\"").with_pos((0, 2)),
+            CTError::new(InputError, "Could not find element: \"Yy\"").with_pos((3, 2))
+                                                                   .with_help("did you mean `Y`?"),
+            CTError::new(InputError, "Found no periodic element"),
+        );
+        let combined = CTError::combine(errors);
+        assert_eq!(combined.desc, "Found 3 errors");
+        assert_eq!(combined.pos, Some((0, 2)));
+        assert_eq!(combined.labels,
+                   vec!(Label { pos: (0, 2), text: "Could not find element: \"Xx\"".to_string() },
+                        Label { pos: (3, 2), text: "Could not find element: \"Yy\"".to_string() }));
+        assert_eq!(combined.help,
+                   Some("did you mean `Y`?; Found no periodic element".to_string()));
+    }
+
+    #[test]
+    fn shift() {
+        let err = CTError::new(InputError, "Missing closing parentheses")
+            .with_pos((4, 1))
+            .with_label((0, 1), "opened here")
+            .shift(5);
+        assert_eq!(err.pos, Some((9, 1)));
+        assert_eq!(err.labels, vec!(Label { pos: (5, 1), text: "opened here".to_string() }));
+    }
+}
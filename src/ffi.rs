@@ -0,0 +1,484 @@
+//! A C interface to the parser, the balancer and the molar mass calculation.
+//!
+//! The functions are declared in `include/chemtool.h`, and are exported by the `cdylib` built
+//! from the crate. Every function which can fail returns 0 on success, and otherwise the exit code
+//! of the kind of error (see `CTErrorKind::exit_code`). The error itself is then stored in `*err`,
+//! unless `err` is null, and must be freed with `ct_error_free`.
+//!
+//! Everything returned through a pointer is owned by the caller, and must be freed by the
+//! matching `ct_*_free` function. Strings returned as `const char *` are borrowed from the object
+//! they were taken from, and live as long as it does.
+//!
+//! A panic must never unwind into the caller, so the functions which can fail report one as an
+//! input error instead.
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+use crate::database::ElemDatabase;
+use crate::elem::{Molecule, Formula};
+use crate::error::{CTError, CTResult};
+use crate::error::CTErrorKind::{InputError, UsageError};
+
+/// A parsed molecule, along with the symbols of its elements as C strings
+pub struct CtMolecule {
+    molecule: Molecule,
+    symbols: Vec<CString>,
+}
+
+/// An error, along with the C strings of its description, labels and help
+pub struct CtError {
+    error: CTError,
+    message: CString,
+    labels: Vec<CString>,
+    help: Option<CString>,
+}
+
+impl CtError {
+    fn new(error: CTError) -> CtError {
+        let labels = error.labels.iter().map(|l| c_string(l.text.as_str())).collect();
+        let help = error.help.as_ref().map(|help| c_string(help.as_str()));
+        CtError { message: c_string(error.desc.as_str()), labels, help, error }
+    }
+}
+
+/// Parses a formula holding a single molecule, e.g. `H2O`, and groups its elements
+///
+/// # Safety
+///
+/// `formula` must be null or a valid C string, and `out` and `err` must be null or valid to
+/// write a pointer to.
+#[no_mangle]
+pub unsafe extern "C" fn ct_parse_molecule(formula: *const c_char, out: *mut *mut CtMolecule,
+                                           err: *mut *mut CtError) -> c_int {
+    guard(err, || {
+        let result = input_str(formula, "formula").and_then(crate::parse_molecule);
+        match result {
+            Ok(molecule) => {
+                let symbols = molecule.iter().map(|e| c_string(e.name.as_str())).collect();
+                write_out(out, Box::into_raw(Box::new(CtMolecule { molecule, symbols })));
+                0
+            },
+            Err(e) => report(err, e),
+        }
+    })
+}
+
+/// Frees a molecule returned by `ct_parse_molecule`
+///
+/// # Safety
+///
+/// `molecule` must be null or a molecule returned by `ct_parse_molecule`, which has not already
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn ct_molecule_free(molecule: *mut CtMolecule) {
+    if !molecule.is_null() {
+        drop(Box::from_raw(molecule));
+    }
+}
+
+/// Returns the number of elements in a molecule, where a charge counts as an element
+///
+/// # Safety
+///
+/// `molecule` must be a valid molecule returned by `ct_parse_molecule`.
+#[no_mangle]
+pub unsafe extern "C" fn ct_molecule_len(molecule: *const CtMolecule) -> usize {
+    (*molecule).molecule.len()
+}
+
+/// Returns the symbol of the element at the given index, or null if there is none
+///
+/// A charge has the symbol `+` or `-`.
+///
+/// # Safety
+///
+/// `molecule` must be a valid molecule returned by `ct_parse_molecule`.
+#[no_mangle]
+pub unsafe extern "C" fn ct_molecule_symbol(molecule: *const CtMolecule, index: usize)
+                                            -> *const c_char {
+    let molecule = &*molecule;
+    molecule.symbols.get(index).map_or(ptr::null(), |symbol| symbol.as_ptr())
+}
+
+/// Returns the count of the element at the given index, or 0 if there is none
+///
+/// For a charge, this is the size of the charge.
+///
+/// # Safety
+///
+/// `molecule` must be a valid molecule returned by `ct_parse_molecule`.
+#[no_mangle]
+pub unsafe extern "C" fn ct_molecule_count(molecule: *const CtMolecule, index: usize) -> u32 {
+    let molecule = &*molecule;
+    molecule.molecule.get(index).map_or(0, |e| e.coef)
+}
+
+/// Writes a molecule as a formula, e.g. `SO4^2-`, which must be freed with `ct_string_free`
+///
+/// # Safety
+///
+/// `molecule` must be a valid molecule returned by `ct_parse_molecule`.
+#[no_mangle]
+pub unsafe extern "C" fn ct_molecule_formula(molecule: *const CtMolecule) -> *mut c_char {
+    c_string(format!("{}", Formula(&(*molecule).molecule)).as_str()).into_raw()
+}
+
+/// Opens the element database at the given path, e.g. `elemdb.csv`
+///
+/// # Safety
+///
+/// `path` must be null or a valid C string, and `out` and `err` must be null or valid to write a
+/// pointer to.
+#[no_mangle]
+pub unsafe extern "C" fn ct_database_open(path: *const c_char, out: *mut *mut ElemDatabase,
+                                          err: *mut *mut CtError) -> c_int {
+    guard(err, || {
+        let result = input_str(path, "database path").and_then(|path| {
+            ElemDatabase::open(Path::new(path))
+        });
+        match result {
+            Ok(database) => {
+                write_out(out, Box::into_raw(Box::new(database)));
+                0
+            },
+            Err(e) => report(err, e),
+        }
+    })
+}
+
+/// Closes a database opened by `ct_database_open`
+///
+/// # Safety
+///
+/// `database` must be null or a database returned by `ct_database_open`, which has not already
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn ct_database_free(database: *mut ElemDatabase) {
+    if !database.is_null() {
+        drop(Box::from_raw(database));
+    }
+}
+
+/// Finds the molar mass of a formula in g/mol, looking up its elements in the database
+///
+/// # Safety
+///
+/// `database` must be null or a valid database returned by `ct_database_open`, `formula` must be
+/// null or a valid C string, `mass` must be null or valid to write a double to, and `err` must be
+/// null or valid to write a pointer to.
+#[no_mangle]
+pub unsafe extern "C" fn ct_molar_mass(database: *mut ElemDatabase, formula: *const c_char,
+                                       mass: *mut f64, err: *mut *mut CtError) -> c_int {
+    guard(err, || {
+        let result = input_str(formula, "formula").and_then(|formula| {
+            match database.as_mut() {
+                Some(database) => crate::molar_mass(database, formula),
+                None => Err(CTError::new(UsageError, "Missing database")),
+            }
+        });
+        match result {
+            Ok(total) => {
+                write_out(mass, total);
+                0
+            },
+            Err(e) => report(err, e),
+        }
+    })
+}
+
+/// Balances a reaction, and writes the coefficient of each species to `coefs`
+///
+/// The coefficients are written with the reactants first, and the number of species is written
+/// to `*count`. If `capacity`, the number of coefficients `coefs` can hold, is too small, nothing
+/// is written to `coefs`, but `*count` is still set, so the call can be retried with a larger
+/// buffer.
+///
+/// # Safety
+///
+/// `reaction` must be null or a valid C string, `coefs` must be valid to write `capacity`
+/// coefficients to, and `count` and `err` must be null or valid to write to.
+#[no_mangle]
+pub unsafe extern "C" fn ct_balance(reaction: *const c_char, coefs: *mut u32, capacity: usize,
+                                    count: *mut usize, err: *mut *mut CtError) -> c_int {
+    guard(err, || {
+        let reaction = match input_str(reaction, "reaction").and_then(crate::balance) {
+            Ok(reaction) => reaction,
+            Err(e) => return report(err, e),
+        };
+        let balanced = reaction.coefs();
+        write_out(count, balanced.len());
+        if balanced.len() > capacity || (coefs.is_null() && !balanced.is_empty()) {
+            let desc = format!("The buffer holds {} coefficients, but the reaction has {} species",
+                               capacity, balanced.len());
+            return report(err, CTError::new(UsageError, desc));
+        }
+        for (i, &coef) in balanced.iter().enumerate() {
+            *coefs.add(i) = coef;
+        }
+        0
+    })
+}
+
+/// Returns the kind of an error, which is the same as the status returned with it
+///
+/// # Safety
+///
+/// `err` must be a valid error returned by one of the functions.
+#[no_mangle]
+pub unsafe extern "C" fn ct_error_kind(err: *const CtError) -> c_int {
+    (*err).error.kind.exit_code()
+}
+
+/// Returns the description of an error
+///
+/// # Safety
+///
+/// `err` must be a valid error returned by one of the functions.
+#[no_mangle]
+pub unsafe extern "C" fn ct_error_message(err: *const CtError) -> *const c_char {
+    (*err).message.as_ptr()
+}
+
+/// Returns the hint on how to fix an error, or null if there is none
+///
+/// # Safety
+///
+/// `err` must be a valid error returned by one of the functions.
+#[no_mangle]
+pub unsafe extern "C" fn ct_error_help(err: *const CtError) -> *const c_char {
+    (*err).help.as_ref().map_or(ptr::null(), |help| help.as_ptr())
+}
+
+/// Finds the position and length of an error in the input, as byte offsets
+///
+/// Returns 1 and writes the span if the error has a position, and otherwise returns 0.
+///
+/// # Safety
+///
+/// `err` must be a valid error returned by one of the functions, and `pos` and `len` must be
+/// null or valid to write to.
+#[no_mangle]
+pub unsafe extern "C" fn ct_error_span(err: *const CtError, pos: *mut usize, len: *mut usize)
+                                       -> c_int {
+    match (*err).error.pos {
+        Some((p, l)) => {
+            write_out(pos, p);
+            write_out(len, l);
+            1
+        },
+        None => 0,
+    }
+}
+
+/// Returns the number of labels of an error, i.e. the other parts of the input it points at
+///
+/// # Safety
+///
+/// `err` must be a valid error returned by one of the functions.
+#[no_mangle]
+pub unsafe extern "C" fn ct_error_label_count(err: *const CtError) -> usize {
+    (*err).labels.len()
+}
+
+/// Returns the text of the label at the given index, and writes its span, or null if there is none
+///
+/// # Safety
+///
+/// `err` must be a valid error returned by one of the functions, and `pos` and `len` must be
+/// null or valid to write to.
+#[no_mangle]
+pub unsafe extern "C" fn ct_error_label(err: *const CtError, index: usize, pos: *mut usize,
+                                        len: *mut usize) -> *const c_char {
+    let err = &*err;
+    match (err.error.labels.get(index), err.labels.get(index)) {
+        (Some(label), Some(text)) => {
+            write_out(pos, label.pos.0);
+            write_out(len, label.pos.1);
+            text.as_ptr()
+        },
+        _ => ptr::null(),
+    }
+}
+
+/// Formats an error as the program prints it, pointing at the error in the input
+///
+/// The input may be null, in which case only the description and help are written. The message
+/// is coloured if `color` is non-zero, and must be freed with `ct_string_free`.
+///
+/// # Safety
+///
+/// `err` must be a valid error returned by one of the functions, and `input` must be null or a
+/// valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn ct_error_render(err: *const CtError, input: *const c_char, color: c_int)
+                                         -> *mut c_char {
+    let input = input_str(input, "input").ok();
+    c_string((*err).error.message(input, color != 0).as_str()).into_raw()
+}
+
+/// Frees an error returned by one of the functions
+///
+/// # Safety
+///
+/// `err` must be null or an error returned by one of the functions, which has not already been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn ct_error_free(err: *mut CtError) {
+    if !err.is_null() {
+        drop(Box::from_raw(err));
+    }
+}
+
+/// Frees a string returned by `ct_molecule_formula` or `ct_error_render`
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by one of those functions, which has not already
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn ct_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Reads a C string given as input, which must not be null and must be valid UTF-8
+unsafe fn input_str<'a>(input: *const c_char, what: &str) -> CTResult<&'a str> {
+    if input.is_null() {
+        return Err(CTError::new(UsageError, format!("Missing {}", what)));
+    }
+    CStr::from_ptr(input).to_str().map_err(|e| {
+        CTError::new(InputError, format!("The {} is not valid UTF-8", what))
+            .with_pos((e.valid_up_to(), 1))
+            .with_source(e)
+    })
+}
+
+/// Runs the body of a function which can fail, reporting a panic in it as an error
+///
+/// Unwinding into C is undefined behaviour, and would abort the program using the library.
+unsafe fn guard<F: FnOnce() -> c_int>(err: *mut *mut CtError, body: F) -> c_int {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(status) => status,
+        Err(_) => report(err, CTError::new(InputError, "Internal error while handling the input")),
+    }
+}
+
+/// Stores an error for the caller, if it asked for it, and returns the status for it
+unsafe fn report(err: *mut *mut CtError, error: CTError) -> c_int {
+    let status = error.kind.exit_code();
+    write_out(err, Box::into_raw(Box::new(CtError::new(error))));
+    status
+}
+
+/// Writes a result through a pointer given by the caller, unless it is null
+///
+/// A pointer to a Box which could not be written is freed again, so nothing leaks.
+unsafe fn write_out<T: Release>(out: *mut T, value: T) {
+    if out.is_null() {
+        value.release();
+    } else {
+        *out = value;
+    }
+}
+
+/// Frees the values which `write_out` can not hand to the caller
+trait Release {
+    fn release(self);
+}
+
+impl<T> Release for *mut T {
+    fn release(self) {
+        // every pointer written by this module comes from Box::into_raw
+        unsafe { drop(Box::from_raw(self)) }
+    }
+}
+
+impl Release for f64 {
+    fn release(self) {}
+}
+
+impl Release for usize {
+    fn release(self) {}
+}
+
+/// Makes a C string, dropping any NUL bytes which would cut it short
+fn c_string(text: &str) -> CString {
+    CString::new(text.replace('\0', "")).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let mut molecule = ptr::null_mut();
+        unsafe {
+            assert_eq!(ct_parse_molecule(c"SO4^2-".as_ptr(), &mut molecule, ptr::null_mut()), 0);
+            assert_eq!(ct_molecule_len(molecule), 3);
+            let formula = ct_molecule_formula(molecule);
+            assert_eq!(CStr::from_ptr(formula).to_str(), Ok("O4S^2-"));
+            ct_string_free(formula);
+            assert!(ct_molecule_symbol(molecule, 3).is_null());
+            ct_molecule_free(molecule);
+        }
+    }
+
+    #[test]
+    fn balance() {
+        let mut coefs = [0; 4];
+        let mut count = 0;
+        let reaction = c"C3H8 + O2 -> CO2 + H2O";
+        unsafe {
+            let status = ct_balance(reaction.as_ptr(), coefs.as_mut_ptr(), coefs.len(), &mut count,
+                                    ptr::null_mut());
+            assert_eq!(status, 0);
+        }
+        assert_eq!(count, 4);
+        assert_eq!(coefs, [1, 5, 3, 4]);
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let mut coefs = [0; 2];
+        let mut count = 0;
+        let mut err = ptr::null_mut();
+        unsafe {
+            let status = ct_balance(c"H2 + O2 -> H2O".as_ptr(), coefs.as_mut_ptr(), coefs.len(),
+                                    &mut count, &mut err);
+            assert_eq!(status, 2);
+            assert_eq!(ct_error_kind(err), 2);
+            ct_error_free(err);
+        }
+        assert_eq!(count, 3);
+        assert_eq!(coefs, [0, 0]);
+    }
+
+    #[test]
+    fn error_span() {
+        let mut molecule = ptr::null_mut();
+        let mut err = ptr::null_mut();
+        let (mut pos, mut len) = (0, 0);
+        unsafe {
+            assert_eq!(ct_parse_molecule(c"H2O)".as_ptr(), &mut molecule, &mut err), 1);
+            assert!(molecule.is_null());
+            assert_eq!(ct_error_span(err, &mut pos, &mut len), 1);
+            assert_eq!(CStr::from_ptr(ct_error_message(err)).to_str(),
+                       Ok("Missing opening parentheses"));
+            ct_error_free(err);
+        }
+        assert_eq!((pos, len), (3, 1));
+    }
+
+    #[test]
+    fn null_input() {
+        let mut molecule = ptr::null_mut();
+        unsafe {
+            assert_eq!(ct_parse_molecule(ptr::null(), &mut molecule, ptr::null_mut()), 2);
+        }
+        assert!(molecule.is_null());
+    }
+}
@@ -0,0 +1,293 @@
+//! Functions for ideal gas law calculations.
+//!
+//! All calculations are based on PV = nRT, with the molar mass of the gas used to convert between
+//! amount and mass, and to find the density of the gas at the given conditions.
+
+use crate::units;
+use crate::json::Json;
+use crate::output;
+use crate::error::{CTResult, CTError};
+use crate::error::CTErrorKind::{InputError, UsageError};
+
+/// The molar gas constant in L·Pa/(mol·K)
+pub const GAS_CONSTANT: f64 = 8314.462618;
+
+/// Standard temperature and pressure (IUPAC): 273.15 K and 100 kPa
+pub const STP: (f64, f64) = (273.15, 100000.0);
+
+/// Standard ambient temperature and pressure: 298.15 K and 100 kPa
+pub const SATP: (f64, f64) = (298.15, 100000.0);
+
+/// The quantities describing an amount of gas. Quantities which are not known are None.
+///
+/// The pressure is in Pa, volume in L, amount in mol, mass in g, temperature in K, and the
+/// density in g/L.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GasData {
+    pub pressure: Option<f64>,
+    pub volume: Option<f64>,
+    pub amount: Option<f64>,
+    pub mass: Option<f64>,
+    pub temperature: Option<f64>,
+    pub density: Option<f64>,
+}
+
+impl GasData {
+    /// Creates a GasData from the arguments given by the user
+    ///
+    /// The arguments are either `key=value` pairs, or one of the presets `STP` and `SATP`, which
+    /// set the temperature and pressure unless these are given explicitly. A value of `?` marks
+    /// the quantity as unknown, which is the same as not giving it at all.
+    pub fn from_args(args: &[String]) -> CTResult<GasData> {
+        let mut data = GasData {
+            pressure: None,
+            volume: None,
+            amount: None,
+            mass: None,
+            temperature: None,
+            density: None,
+        };
+        let mut preset = None;
+        let mut rest = Vec::new();
+        for arg in args.iter() {
+            match arg.as_str() {
+                "STP" | "stp" => preset = Some(STP),
+                "SATP" | "satp" => preset = Some(SATP),
+                _ => rest.push(arg.clone()),
+            }
+        }
+
+        let assigns = units::parse_assignments(&rest)?;
+        for (key, value) in assigns.iter() {
+            if value.as_str() == "?" {
+                continue;
+            }
+            let value = value.as_str();
+            match key.as_str() {
+                "P" | "pressure" => {
+                    data.pressure = Some(units::parse_quantity(value, units::PRESSURE)?);
+                },
+                "V" | "volume" => {
+                    data.volume = Some(units::parse_quantity(value, units::VOLUME)?);
+                },
+                "n" | "amount" => {
+                    data.amount = Some(units::parse_quantity(value, units::AMOUNT)?);
+                },
+                "m" | "mass" => {
+                    data.mass = Some(units::parse_quantity(value, units::MASS)?);
+                },
+                "T" | "temperature" => {
+                    data.temperature = Some(units::parse_temperature(value)?);
+                },
+                _ => {
+                    return Err(CTError::new(UsageError, format!("Unknown gas quantity: {:?}", key)))
+                },
+            }
+        }
+
+        if let Some((temp, pressure)) = preset {
+            data.temperature = data.temperature.or(Some(temp));
+            data.pressure = data.pressure.or(Some(pressure));
+        }
+        Ok(data)
+    }
+}
+
+/// Finds the missing quantity in PV = nRT, and the density of the gas
+///
+/// At most one of pressure, volume, amount (or mass) and temperature may be unknown. The only
+/// exception is when both the volume and amount are unknown, in which case only the density can
+/// be found. In any other case, if any of the given quantities are not positive, or if more
+/// quantities are given than needed and they disagree, the function errors.
+pub fn solve(mut data: GasData, molar_mass: f64) -> CTResult<GasData> {
+    if let Some(t) = data.temperature {
+        units::check_temperature(t)?;
+    }
+    units::check_positive("pressure", data.pressure)?;
+    units::check_positive("volume", data.volume)?;
+    units::check_positive("amount", data.amount)?;
+    units::check_positive("mass", data.mass)?;
+    match (data.amount, data.mass) {
+        (Some(n), Some(m)) if !units::roughly_equal(m, n * molar_mass) => {
+            return Err(inconsistent("m = n * M"))
+        },
+        (None, _) => data.amount = data.mass.map(|m| m / molar_mass),
+        _ => (),
+    }
+
+    let r = GAS_CONSTANT;
+    match (data.pressure, data.volume, data.amount, data.temperature) {
+        (None, Some(v), Some(n), Some(t)) => data.pressure = Some(n * r * t / v),
+        (Some(p), None, Some(n), Some(t)) => data.volume = Some(n * r * t / p),
+        (Some(p), Some(v), None, Some(t)) => data.amount = Some(p * v / (r * t)),
+        (Some(p), Some(v), Some(n), None) => data.temperature = Some(p * v / (n * r)),
+        (Some(p), Some(v), Some(n), Some(t)) => {
+            if !units::roughly_equal(p * v, n * r * t) {
+                return Err(inconsistent("PV = nRT"))
+            }
+        },
+        (Some(_), None, None, Some(_)) => (),
+        _ => {
+            return Err(CTError::new(InputError,
+                                    "At most one of P, V, n (or m) and T may be unknown"))
+        },
+    }
+
+    if data.mass.is_none() {
+        data.mass = data.amount.map(|n| n * molar_mass);
+    }
+    if let (Some(p), Some(t)) = (data.pressure, data.temperature) {
+        data.density = Some(p * molar_mass / (r * t));
+    }
+    Ok(data)
+}
+
+fn inconsistent(relation: &str) -> CTError {
+    let desc = format!("The given quantities are inconsistent: {} does not hold", relation);
+    CTError::new(InputError, desc).with_help("leave out one of the quantities")
+}
+
+/// Pretty prints the known quantities of a gas
+pub fn pretty_print(data: &GasData, molar_mass: f64) {
+    println!("M:             {:.5} g/mol", molar_mass);
+    if let Some(p) = data.pressure {
+        println!("pressure:      {:.5} kPa ({:.5} atm)", p / 1e3, p / 101325.0);
+    }
+    if let Some(t) = data.temperature {
+        println!("temperature:   {:.2} K ({:.2} °C)", t, t - 273.15);
+    }
+    print_quantity("volume:", data.volume, "L");
+    print_quantity("amount:", data.amount, "mol");
+    print_quantity("mass:", data.mass, "g");
+    print_quantity("density:", data.density, "g/L");
+}
+
+/// Makes the rows of a table with the known quantities of a gas
+pub fn to_rows(data: &GasData, molar_mass: f64) -> Vec<Vec<String>> {
+    output::quantity_rows(&[
+        ("molar_mass", Some(molar_mass), "g/mol"),
+        ("pressure", data.pressure, "Pa"),
+        ("temperature", data.temperature, "K"),
+        ("volume", data.volume, "L"),
+        ("amount", data.amount, "mol"),
+        ("mass", data.mass, "g"),
+        ("density", data.density, "g/L"),
+    ])
+}
+
+/// Makes a JSON object with the quantities of a gas, where unknown quantities are null
+pub fn to_json(data: &GasData, molar_mass: f64) -> Json {
+    Json::object(vec!(
+        ("molar_mass", Json::Number(molar_mass)),
+        ("pressure", Json::number(data.pressure)),
+        ("temperature", Json::number(data.temperature)),
+        ("volume", Json::number(data.volume)),
+        ("amount", Json::number(data.amount)),
+        ("mass", Json::number(data.mass)),
+        ("density", Json::number(data.density)),
+    ))
+}
+
+fn print_quantity(name: &str, value: Option<f64>, unit: &str) {
+    if let Some(value) = value {
+        println!("{: <14} {:.5} {}", name, value, unit);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn assert_close(result: Option<f64>, expected: f64) {
+        let result = result.unwrap();
+        assert!((result - expected).abs() < 1e-3, "{} != {}", result, expected);
+    }
+
+    #[test]
+    fn molar_volume() {
+        let data = GasData::from_args(&args(&["STP", "n=1mol"])).unwrap();
+        let result = solve(data, 31.9988).unwrap();
+        assert_close(result.volume, 22.711);
+    }
+
+    #[test]
+    fn pressure_from_mass() {
+        // 32 g of O2 at 0 °C in 22.4 L is very close to 1 atm
+        let data = GasData::from_args(&args(&["m=31.9988g", "V=22.414L", "T=0°C"])).unwrap();
+        let result = solve(data, 31.9988).unwrap();
+        assert_close(result.pressure.map(|p| p / 101325.0), 1.0);
+    }
+
+    #[test]
+    fn temperature() {
+        let data = GasData::from_args(&args(&["P=1atm", "V=24.4654L", "n=1mol"])).unwrap();
+        let result = solve(data, 28.0134).unwrap();
+        assert_close(result.temperature, 298.15);
+    }
+
+    #[test]
+    fn density_only() {
+        let data = GasData::from_args(&args(&["SATP"])).unwrap();
+        let result = solve(data, 28.0134).unwrap();
+        assert_close(result.density, 1.13005);
+        assert_eq!(result.volume, None);
+    }
+
+    #[test]
+    fn explicit_overrides_preset() {
+        let data = GasData::from_args(&args(&["T=300K", "STP"])).unwrap();
+        assert_eq!(data.temperature, Some(300.0));
+        assert_eq!(data.pressure, Some(100000.0));
+    }
+
+    #[test]
+    fn too_many_unknowns() {
+        let data = GasData::from_args(&args(&["T=300K", "n=2mol"])).unwrap();
+        assert!(solve(data, 1.0).is_err());
+    }
+
+    #[test]
+    fn absolute_zero() {
+        let data = GasData::from_args(&args(&["T=-273.15°C", "P=1atm", "n=1mol"])).unwrap();
+        assert_eq!(solve(data, 1.0).unwrap_err().desc,
+                   "The temperature must be above absolute zero, found: 0.00 K");
+    }
+
+    #[test]
+    fn not_positive() {
+        let data = GasData::from_args(&args(&["STP", "V=0L"])).unwrap();
+        assert_eq!(solve(data, 1.0).unwrap_err().desc, "The volume must be positive, found: 0");
+        let data = GasData::from_args(&args(&["STP", "m=-2g"])).unwrap();
+        assert!(solve(data, 1.0).is_err());
+    }
+
+    #[test]
+    fn not_finite() {
+        let data = GasData::from_args(&args(&["P=1atm", "n=1mol"])).unwrap();
+        let result = solve(GasData { temperature: Some(f64::NAN), ..data.clone() }, 1.0);
+        assert_eq!(result.unwrap_err().desc, "Expected a finite temperature, found: NaN");
+        assert!(solve(GasData { temperature: Some(f64::INFINITY), ..data }, 1.0).is_err());
+    }
+
+    #[test]
+    fn inconsistent_quantities() {
+        let data = GasData::from_args(&args(&["STP", "V=22.711L", "n=2mol"])).unwrap();
+        assert_eq!(solve(data, 31.9988).unwrap_err().desc,
+                   "The given quantities are inconsistent: PV = nRT does not hold");
+        let data = GasData::from_args(&args(&["STP", "n=1mol", "m=10g"])).unwrap();
+        assert_eq!(solve(data, 31.9988).unwrap_err().desc,
+                   "The given quantities are inconsistent: m = n * M does not hold");
+        // rounded quantities are still accepted
+        let data = GasData::from_args(&args(&["STP", "V=22.7L", "n=1mol", "m=32g"])).unwrap();
+        assert_close(solve(data, 31.9988).unwrap().density, 1.40897);
+    }
+
+    #[test]
+    fn unknown_quantity() {
+        assert!(GasData::from_args(&args(&["x=300K"])).is_err());
+    }
+}
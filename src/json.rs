@@ -0,0 +1,113 @@
+//! A minimal JSON value type, used for machine readable output.
+//!
+//! Only writing JSON is supported, since chemtool never has to read it.
+
+use std::fmt::{Display, Formatter, Error};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Makes a JSON object from (key, value) pairs, keeping the order of the keys
+    pub fn object(pairs: Vec<(&str, Json)>) -> Json {
+        Json::Object(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    /// Makes a JSON string
+    pub fn string<T: Display>(value: T) -> Json {
+        Json::String(format!("{}", value))
+    }
+
+    /// Makes a JSON number, or null if there is no value
+    pub fn number(value: Option<f64>) -> Json {
+        value.map_or(Json::Null, Json::Number)
+    }
+}
+
+impl Display for Json {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        match *self {
+            Json::Null => fmt.write_str("null"),
+            Json::Bool(b) => write!(fmt, "{}", b),
+            // JSON has no representation of infinity or NaN
+            Json::Number(n) if n.is_finite() => write!(fmt, "{}", n),
+            Json::Number(_) => fmt.write_str("null"),
+            Json::String(ref s) => write_string(fmt, s),
+            Json::Array(ref items) => {
+                fmt.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        fmt.write_str(",")?;
+                    }
+                    write!(fmt, "{}", item)?;
+                }
+                fmt.write_str("]")
+            },
+            Json::Object(ref pairs) => {
+                fmt.write_str("{")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        fmt.write_str(",")?;
+                    }
+                    write_string(fmt, key)?;
+                    write!(fmt, ":{}", value)?;
+                }
+                fmt.write_str("}")
+            },
+        }
+    }
+}
+
+fn write_string(fmt: &mut Formatter, s: &str) -> Result<(), Error> {
+    fmt.write_str("\"")?;
+    for ch in s.chars() {
+        match ch {
+            '"' => fmt.write_str("\\\"")?,
+            '\\' => fmt.write_str("\\\\")?,
+            '\n' => fmt.write_str("\\n")?,
+            '\r' => fmt.write_str("\\r")?,
+            '\t' => fmt.write_str("\\t")?,
+            ch if (ch as u32) < 0x20 => write!(fmt, "\\u{:04x}", ch as u32)?,
+            ch => write!(fmt, "{}", ch)?,
+        }
+    }
+    fmt.write_str("\"")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scalars() {
+        assert_eq!(format!("{}", Json::Null), "null");
+        assert_eq!(format!("{}", Json::Bool(true)), "true");
+        assert_eq!(format!("{}", Json::Number(1.5)), "1.5");
+        assert_eq!(format!("{}", Json::Number(3.0)), "3");
+    }
+
+    #[test]
+    fn not_finite() {
+        assert_eq!(format!("{}", Json::Number(1.0 / 0.0)), "null");
+    }
+
+    #[test]
+    fn escapes() {
+        let json = Json::string("a \"b\"\\\n\u{1}");
+        assert_eq!(format!("{}", json), "\"a \\\"b\\\"\\\\\\n\\u0001\"");
+    }
+
+    #[test]
+    fn nested() {
+        let json = Json::object(vec!(("a", Json::Array(vec!(Json::Number(1.0), Json::Null))),
+                                     ("b", Json::object(vec!()))));
+        assert_eq!(format!("{}", json), "{\"a\":[1,null],\"b\":{}}");
+    }
+}
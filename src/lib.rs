@@ -0,0 +1,115 @@
+//! Parsing, balancing and molar masses of chemical formulas and reactions.
+//!
+//! This is the library behind the `chemtool` command line program. The functions at the top of
+//! the crate are the main entry points, and return their results as data instead of printing
+//! them:
+//! * `parse_molecule` and `parse_reaction` parse formulas, e.g. `CuSO4·5H2O`, and reactions,
+//!   e.g. `C3H8 + O2 -> CO2 + H2O`.
+//! * `balance` finds the coefficients which balance a reaction.
+//! * `molar_mass` and `element` look up the elements of a formula in an `ElemDatabase`.
+//!
+//! A reaction is a `Reaction`, which holds each `Species` with its molecule, coefficient and
+//! state, along with the kind of arrow and the conditions of the reaction. It can balance,
+//! check and reverse itself, and its `Display` writes it the way it would be typed.
+//!
+//! With the `serde` feature, molecules, reactions and element data implement `Serialize` and
+//! `Deserialize`, using the same field names as the JSON written by the program.
+//!
+//! The `ffi` module exposes parsing, balancing and molar masses to C and C++, through the
+//! `cdylib` built from the crate and the header in `include/chemtool.h`. The `python` feature
+//! builds a Python extension module in `python`, and the `wasm` feature exports a JavaScript API
+//! in `wasm`, which uses the element table built into the library (`ElemDatabase::embedded`).
+//!
+//! The `arith` module adds, subtracts, scales and compares molecules by their element counts,
+//! e.g. finding that `C6H12O6` loses `H2O` on its way to `C6H10O5`.
+//!
+//! The modules hold the rest of what the program does, e.g. solutions, gases and
+//! thermodynamics, along with the functions for writing the results as text, JSON or tables.
+//!
+//! ```
+//! let mut reaction = chemtool::balance("C3H8 + O2 -> CO2 + H2O").unwrap();
+//! assert_eq!(reaction.coefs(), vec!(1, 5, 3, 4));
+//! assert_eq!(reaction.to_string(), "C3H8 + 5 O2 -> 3 CO2 + 4 H2O");
+//! assert!(reaction.check().is_ok());
+//!
+//! reaction.reverse();
+//! assert_eq!(reaction.to_string(), "3 CO2 + 4 H2O -> C3H8 + 5 O2");
+//! ```
+//!
+//! Every error is a `CTError`, which implements `std::error::Error`. Besides its description, it
+//! can be formatted as the full message the program prints, pointing at the error in the input:
+//!
+//! ```
+//! let err = chemtool::parse_molecule("H2O)").unwrap_err();
+//! assert_eq!(err.to_string(), "Missing opening parentheses");
+//! assert_eq!(err.message(Some("H2O)"), false),
+//!            "Missing opening parentheses\n    H2O)\n       ^\n");
+//! ```
+
+pub use elem::{PerElem, Molecule, Species, Reaction, Arrow, State};
+pub use database::{ElemDatabase, ElemData};
+pub use error::{CTError, CTErrorKind, CTResult, Label};
+
+use crate::parser::Parser;
+
+pub mod elem;
+pub mod parser;
+pub mod error;
+pub mod database;
+pub mod mass;
+pub mod balance;
+pub mod units;
+pub mod solution;
+pub mod gas;
+pub mod thermo;
+pub mod compounds;
+pub mod canon;
+pub mod json;
+pub mod output;
+pub mod calc;
+pub mod render;
+pub mod suggest;
+pub mod arith;
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+/// Parses a formula holding a single molecule, e.g. `H2O`, and groups its elements
+///
+/// A charge, e.g. in `SO4^2-`, is kept as a PerElem named `+` or `-`.
+pub fn parse_molecule(input: &str) -> CTResult<Molecule> {
+    parser::parse_single_molecule(input)
+}
+
+/// Parses a chemical reaction, e.g. `2 H2(g) + O2(g) -> 2 H2O(l)`, into its reactants and products
+///
+/// Species without a coefficient in the input get a coefficient of 1.
+pub fn parse_reaction(input: &str) -> CTResult<Reaction> {
+    Parser::new(input).parse_reaction()
+}
+
+/// Parses and balances a chemical reaction
+///
+/// Any coefficients given in the input are replaced by the balanced ones.
+pub fn balance(input: &str) -> CTResult<Reaction> {
+    let mut reaction = parse_reaction(input)?;
+    reaction.balance()?;
+    Ok(reaction)
+}
+
+/// Finds the molar mass of a formula in g/mol
+///
+/// The charge of the molecule, if any, does not count towards its mass.
+pub fn molar_mass(database: &mut ElemDatabase, input: &str) -> CTResult<f64> {
+    let molecule = elem::strip_charge(parse_molecule(input)?);
+    let data = database.get_data(&molecule)?;
+    Ok(mass::molar_mass(&data, &molecule))
+}
+
+/// Looks up a single element by its symbol, e.g. `Fe`
+pub fn element(database: &mut ElemDatabase, symbol: &str) -> CTResult<ElemData> {
+    let elem = PerElem { name: symbol.to_string(), coef: 1, pos: 0, len: symbol.len() };
+    database.get_single_data(&elem)
+}
@@ -1,42 +1,78 @@
-#![allow(unused_features)] // so we can still feature(os) when testing
-#![feature(collections, path, io, core, os, plugin, env)]
-extern crate getopts;
-
 use getopts::Options;
 use std::env;
-use parser::Parser;
-use database::ElemDatabase;
-use error::{CTResult, CTError};
-use error::CTErrorKind::{InputError, UsageError};
-
-mod elem;
-mod parser;
-mod error;
-mod database;
-mod mass;
-mod balance;
-
-const USAGE: &'static str = "\
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicI32, Ordering};
+use chemtool::{elem, parser, mass, balance, units, solution, gas, thermo, canon, output, calc,
+               render, suggest, arith};
+use chemtool::parser::Parser;
+use chemtool::database::ElemDatabase;
+use chemtool::compounds::CompoundDatabase;
+use chemtool::output::Format;
+use chemtool::render::Style;
+use chemtool::json::Json;
+use chemtool::elem::{Molecule, Formula};
+use chemtool::error::{CTResult, CTError};
+use chemtool::error::CTErrorKind::{InputError, UsageError};
+
+mod repl;
+
+const USAGE: &str = "\
 Usage:
     chemtool mass <formula> [options]
     chemtool balance <reaction> [options]
+    chemtool (mass | balance) (- | --input PATH) [options]
+    chemtool solution [<formula>] <quantity>=<value>... [options]
+    chemtool gas <formula> [STP | SATP] <quantity>=<value>... [options]
+    chemtool thermo <reaction> [T=<temperature>] [options]
+    chemtool normalize <formula | reaction> [options]
+    chemtool diff <formula> <formula> [options]
+    chemtool calc <expression> [options]
+    chemtool repl [options]
     chemtool [-h | --help]
     chemtool [-v | --version]";
 
-const VERSION: &'static str = "chemtool 0.4.1";
+const VERSION: &str = "chemtool 0.4.1";
+
+/// The status the program exits with, which is set when a command fails
+static EXIT_STATUS: AtomicI32 = AtomicI32::new(0);
+
+fn main() -> ExitCode {
+    run();
+    ExitCode::from(EXIT_STATUS.load(Ordering::SeqCst) as u8)
+}
+
+fn set_exit_status(status: i32) {
+    EXIT_STATUS.store(status, Ordering::SeqCst);
+}
 
-#[cfg(not(test))]
-fn main() {
+fn run() {
     let args: Vec<String> = env::args().collect();
     let mut opts = Options::new();
     opts.optflag("h", "help", "Display this message and then exit.");
     opts.optflag("v", "version", "Display the version number and then exit.");
     opts.optopt("", "db-path", "Explicitly specify the path to the database file.", "PATH");
-    let given_opts = match opts.parse(args.tail()) {
+    opts.optopt("", "thermo-db-path",
+                "Explicitly specify the path to the thermodynamic database file.", "PATH");
+    opts.optflag("", "hill", "Use Hill order for every formula when normalizing.");
+    opts.optopt("", "compounds-path",
+                "Explicitly specify the path to the compound name database file.", "PATH");
+    opts.optopt("", "format", "Write the output as 'text' (default), 'json', 'csv' or 'tsv'.",
+                "FORMAT");
+    opts.optopt("", "render",
+                "Write formulas as 'latex', 'mhchem', 'html' or 'unicode' instead of plain text.",
+                "STYLE");
+    opts.optopt("", "input",
+                "Read formulas or reactions from a file, one per line, instead of the command \
+                 line. Use '-' as the formula or reaction to read them from stdin.", "PATH");
+    let given_opts = match opts.parse(&args[1..]) {
         Ok(go) => go,
         Err(msg) => {
-            println!("{}", msg.to_string());
-            println!("{}", opts.usage(USAGE));
+            eprintln!("{}\n{}", msg, opts.usage(USAGE));
+            set_exit_status(UsageError.exit_code());
             return;
         },
     };
@@ -46,99 +82,535 @@ fn main() {
         println!("{}", VERSION);
     } else {
         let path = if let Some(path) = given_opts.opt_str("db-path") {
-            Path::new(path)
+            PathBuf::from(path)
         } else {
-            let mut path = Path::new(&args[0]);
-            path.set_filename("elemdb.csv");
-            path
+            PathBuf::from(&args[0]).with_file_name("elemdb.csv")
+        };
+        let thermo_path = if let Some(path) = given_opts.opt_str("thermo-db-path") {
+            PathBuf::from(path)
+        } else {
+            PathBuf::from(&args[0]).with_file_name("thermodb.csv")
+        };
+        let compounds_path = if let Some(path) = given_opts.opt_str("compounds-path") {
+            PathBuf::from(path)
+        } else {
+            PathBuf::from(&args[0]).with_file_name("compounds.csv")
         };
 
-        let cmd_result = if given_opts.free.len() > 0 {
-            let cmd = &given_opts.free[0];
-            let args = given_opts.free.tail();
-            match cmd.as_slice() {
-                "mass" => mass_cmd(&args, &path),
-                "balance" => balance_cmd(&args),
+        let format_name = given_opts.opt_str("format").unwrap_or("text".to_string());
+        let (format, format_err) = match Format::from_name(format_name.as_str()) {
+            Ok(format) => (format, None),
+            Err(e) => (Format::Text, Some(e)),
+        };
+        let style_name = given_opts.opt_str("render").unwrap_or("plain".to_string());
+        let (style, style_err) = match Style::from_name(style_name.as_str()) {
+            Ok(style) => (style, None),
+            Err(e) => (Style::Plain, Some(e)),
+        };
+
+        // the first argument after the command is always the formula or reaction to work on,
+        // so that is where we look for compound names
+        let mut cmd_args: Vec<String> = given_opts.free.iter()
+                                                       .skip(1).cloned()
+                                                       .collect();
+        if let (Some(cmd), Some(input)) = (given_opts.free.first(), cmd_args.first_mut()) {
+            *input = resolve_names(cmd.as_str(), input.as_str(), &path, &compounds_path, format);
+        }
+        // diff is the only command working on two formulas
+        if given_opts.free.first().map(|cmd| cmd.as_str()) == Some("diff") {
+            if let Some(input) = cmd_args.get_mut(1) {
+                *input = resolve_names("diff", input.as_str(), &path, &compounds_path, format);
+            }
+        }
+
+        // a formula or reaction of '-' means that they should be read from stdin instead
+        let batch_source = match given_opts.opt_str("input") {
+            Some(path) => Some(path),
+            None if cmd_args.len() == 1 && cmd_args[0].as_str() == "-" => Some("-".to_string()),
+            None => None,
+        };
+
+        let cmd_result = if let Some(e) = format_err.or(style_err) {
+            Err(e)
+        } else if let Some(cmd) = given_opts.free.first() {
+            let args = cmd_args.as_slice();
+            match cmd.as_str() {
+                "mass" | "balance" if batch_source.is_some() => {
+                    let source = batch_source.as_ref().unwrap().as_str();
+                    batch_cmd(cmd.as_str(), args, source, &path, &compounds_path, format)
+                },
+                "mass" => mass_cmd(args, &path, format, style),
+                "balance" => balance_cmd(args, &path, format, style),
+                "solution" => solution_cmd(args, &path, format),
+                "gas" => gas_cmd(args, &path, format),
+                "thermo" => thermo_cmd(args, &thermo_path, format),
+                "calc" => calc_cmd(args, &path, &compounds_path, format),
+                "repl" => repl_cmd(args, &path, &compounds_path),
+                "normalize" => {
+                    let order = if given_opts.opt_present("hill") {
+                        Some(canon::Order::Hill)
+                    } else {
+                        None
+                    };
+                    normalize_cmd(args, order, format)
+                },
+                "diff" => diff_cmd(args, format),
                 _ => {
-                    Err(CTError {
-                        kind: UsageError,
-                        desc: "Invalid command".to_string(),
-                        pos: None,
-                    })
+                    Err(CTError::new(UsageError, "Invalid command"))
                 }
             }
         } else {
-            Err(CTError {
-                kind: UsageError,
-                desc: "Missing command.".to_string(),
-                pos: None,
-            })
+            Err(CTError::new(UsageError, "Missing command."))
         };
 
+        // the errors of diff point into its two formulas, as if written after each other
+        let error_input = if given_opts.free.first().map(|cmd| cmd.as_str()) == Some("diff") {
+            Some(cmd_args.join(" "))
+        } else {
+            cmd_args.first().cloned()
+        };
         match cmd_result {
-            Err(ref e) if e.kind == InputError => e.print(Some(&args[2])),
-            Err(ref e) if e.kind == UsageError => e.print(Some(&opts.usage(USAGE))),
+            Err(ref e) if format == Format::Json => {
+                eprintln!("{}", e.to_json());
+            },
+            Err(ref e) if e.kind == InputError => e.print(error_input.as_deref()),
+            Err(ref e) if e.kind == UsageError => e.print(Some(opts.usage(USAGE).as_str())),
             Err(ref e) => e.print(None),
             _ => ()
         }
+        // scripts can tell what went wrong from the exit status, without parsing the message
+        if let Err(ref e) = cmd_result {
+            set_exit_status(e.kind.exit_code());
+        }
     }
 }
 
-fn mass_cmd(args: &[String], db_path: &Path) -> CTResult<()> {
-    if args.len() < 1 {
-        Err(CTError {
-            kind: UsageError,
-            desc: "Missing formula.".to_string(),
-            pos: None,
-        })
+fn mass_cmd(args: &[String], db_path: &Path, format: Format, style: Style) -> CTResult<()> {
+    if args.is_empty() {
+        Err(CTError::new(UsageError, "Missing formula."))
     } else if args.len() > 1 {
-        Err(CTError {
-            kind: UsageError,
-            desc: "Too many arguments.".to_string(),
-            pos: None,
-        })
+        Err(CTError::new(UsageError, "Too many arguments."))
     } else {
-        let input = args[0].as_slice();
-        let mut parser = Parser::new(input);
-        let molecule = try!(parser.parse_molecule());
-        if !parser.is_done() {
-            // since there should be no whitespace in a molecule, the only way for parser to have
-            // returned sucess while not being done, is if there was some whitespace,
-            // followed by more (illegal) input
-            return Err(CTError {
-                kind: InputError,
-                desc: "A molecule must not contain whitespace".to_string(),
-                pos: None,
-            })
-        }
-
-        let molecule = elem::group_elems(molecule);
-        let mut database = try!(ElemDatabase::open(db_path));
-        let data = try!(database.get_data(&molecule));
-        mass::pretty_print_data(&data, &molecule);
+        let input = args[0].as_str();
+        let parsed = parser::parse_single_molecule(input).map_err(|e| {
+            suggest_symbols(e, input, db_path)
+        })?;
+        let molecule = elem::strip_charge(parsed.clone());
+        let mut database = ElemDatabase::open(db_path)?;
+        let data = match database.get_data(&molecule) {
+            Ok(data) => data,
+            Err(e) => return Err(suggest::add_help(e, input, &mut database)),
+        };
+        if let Some(warning) = suggest::ambiguity(input, &mut database) {
+            if format == Format::Json {
+                eprintln!("{}", Json::object(vec!(("warning", Json::String(warning)))));
+            } else {
+                eprintln!("warning: {}", warning);
+            }
+        }
+        match format {
+            Format::Text => {
+                if style != Style::Plain {
+                    // the grouped molecule is sorted alphabetically, which is not how formulas
+                    // are usually written
+                    let canonical = canon::canonicalize(parsed.clone(),
+                                                        canon::default_order(&parsed));
+                    println!("{}", render::render_molecule(&canonical, style));
+                }
+                mass::pretty_print_data(&data, &molecule)
+            },
+            Format::Json => println!("{}", mass::to_json(&data, &parsed)),
+            Format::Csv | Format::Tsv => {
+                output::print_table(format, &mass::TABLE_HEADER, &mass::to_rows(&data, &molecule))
+            },
+        }
         Ok(())
     }
 }
 
-fn balance_cmd(args: &[String]) -> CTResult<()> {
-    if args.len() < 1 {
-        Err(CTError {
-            kind: UsageError,
-            desc: "Missing reaction.".to_string(),
-            pos: None,
-        })
+fn balance_cmd(args: &[String], db_path: &Path, format: Format, style: Style) -> CTResult<()> {
+    if args.is_empty() {
+        Err(CTError::new(UsageError, "Missing reaction."))
     } else if args.len() > 1 {
-        Err(CTError {
-            kind: UsageError,
-            desc: "Too many arguments.".to_string(),
-            pos: None,
-        })
+        Err(CTError::new(UsageError, "Too many arguments."))
     } else {
-        let input = args[0].as_slice();
+        let input = args[0].as_str();
         let mut parser = Parser::new(input);
-        let reaction = try!(parser.parse_reaction());
-        let coefs = try!(balance::balance_reaction(&reaction));
-        balance::pretty_print_balanced(&reaction, &coefs);
+        let mut reaction = parser.parse_reaction().map_err(|e| {
+            suggest_symbols(e, input, db_path)
+        })?;
+        reaction.balance()?;
+        match format {
+            Format::Text => println!("{}", render::render_balanced(&reaction, style)),
+            Format::Json => println!("{}", balance::to_json(&reaction)),
+            Format::Csv | Format::Tsv => {
+                let rows = balance::to_rows(&reaction);
+                output::print_table(format, &balance::TABLE_HEADER, &rows)
+            },
+        }
+        Ok(())
+    }
+}
+
+fn solution_cmd(args: &[String], db_path: &Path, format: Format) -> CTResult<()> {
+    if args.is_empty() {
+        Err(CTError::new(UsageError, "Missing formula or quantities."))
+    } else if args[0].contains("=") {
+        // without a formula the only thing we can do is a dilution
+        let assigns = units::parse_assignments(args)?;
+        let dilution = solution::Dilution::from_assignments(&assigns)?;
+        let dilution = solution::solve_dilution(dilution)?;
+        match format {
+            Format::Text => solution::pretty_print_dilution(&dilution),
+            Format::Json => println!("{}", solution::dilution_to_json(&dilution)),
+            Format::Csv | Format::Tsv => {
+                let rows = solution::dilution_to_rows(&dilution);
+                output::print_table(format, &output::QUANTITY_HEADER, &rows)
+            },
+        }
+        Ok(())
+    } else {
+        let molecule = elem::strip_charge(parser::parse_single_molecule(args[0].as_str())?);
+        let assigns = units::parse_assignments(&args[1..])?;
+        let given = solution::SolutionData::from_assignments(&assigns)?;
+        let mut database = ElemDatabase::open(db_path)?;
+        let data = database.get_data(&molecule)?;
+        let molar_mass = mass::molar_mass(&data, &molecule);
+        let solved = solution::solve(given, molar_mass)?;
+        match format {
+            Format::Text => solution::pretty_print(&solved, molar_mass),
+            Format::Json => println!("{}", solution::to_json(&solved, molar_mass)),
+            Format::Csv | Format::Tsv => {
+                let rows = solution::to_rows(&solved, molar_mass);
+                output::print_table(format, &output::QUANTITY_HEADER, &rows)
+            },
+        }
+        Ok(())
+    }
+}
+
+fn gas_cmd(args: &[String], db_path: &Path, format: Format) -> CTResult<()> {
+    if args.is_empty() {
+        Err(CTError::new(UsageError, "Missing formula."))
+    } else {
+        let molecule = elem::strip_charge(parser::parse_single_molecule(args[0].as_str())?);
+        let given = gas::GasData::from_args(&args[1..])?;
+        let mut database = ElemDatabase::open(db_path)?;
+        let data = database.get_data(&molecule)?;
+        let molar_mass = mass::molar_mass(&data, &molecule);
+        let solved = gas::solve(given, molar_mass)?;
+        match format {
+            Format::Text => gas::pretty_print(&solved, molar_mass),
+            Format::Json => println!("{}", gas::to_json(&solved, molar_mass)),
+            Format::Csv | Format::Tsv => {
+                let rows = gas::to_rows(&solved, molar_mass);
+                output::print_table(format, &output::QUANTITY_HEADER, &rows)
+            },
+        }
+        Ok(())
+    }
+}
+
+fn thermo_cmd(args: &[String], thermo_path: &Path, format: Format) -> CTResult<()> {
+    if args.is_empty() {
+        Err(CTError::new(UsageError, "Missing reaction."))
+    } else if args.len() > 2 {
+        Err(CTError::new(UsageError, "Too many arguments."))
+    } else {
+        let temp = if args.len() > 1 {
+            let assigns = units::parse_assignments(&args[1..])?;
+            match assigns[0] {
+                (ref key, ref value) if key.as_str() == "T" => {
+                    let temp = units::parse_temperature(value.as_str())?;
+                    // the Gibbs energy and equilibrium constant are meaningless at or below 0 K
+                    units::check_temperature(temp)?;
+                    Some(temp)
+                },
+                (ref key, _) => {
+                    let desc = format!("Unknown thermodynamic quantity: {:?}", key);
+                    return Err(CTError::new(UsageError, desc));
+                },
+            }
+        } else {
+            None
+        };
+
+        let mut parser = Parser::new(args[0].as_str());
+        let mut reaction = parser.parse_reaction()?;
+        reaction.balance()?;
+        let database = thermo::ThermoDatabase::open(thermo_path)?;
+        let species = database.get_reaction_data(&reaction)?;
+        let result = thermo::reaction_thermo(&reaction, &species);
+        match format {
+            Format::Text => thermo::pretty_print(&reaction, &result, temp),
+            Format::Json => println!("{}", thermo::to_json(&reaction, &result, temp)),
+            Format::Csv | Format::Tsv => {
+                let rows = thermo::to_rows(&result, temp);
+                output::print_table(format, &thermo::TABLE_HEADER, &rows)
+            },
+        }
+        Ok(())
+    }
+}
+
+fn normalize_cmd(args: &[String], order: Option<canon::Order>, format: Format)
+                 -> CTResult<()> {
+    if args.is_empty() {
+        Err(CTError::new(UsageError, "Missing formula or reaction."))
+    } else if args.len() > 1 {
+        Err(CTError::new(UsageError, "Too many arguments."))
+    } else {
+        let canonical = |molecule: Molecule| {
+            let order = order.unwrap_or(canon::default_order(&molecule));
+            canon::canonicalize(molecule, order)
+        };
+        let input = args[0].as_str();
+        let normalized = if parser::is_reaction(input) {
+            let mut reaction = Parser::new(input).parse_reaction()?;
+            for species in reaction.reactants.iter_mut().chain(reaction.products.iter_mut()) {
+                species.molecule = canonical(species.molecule.clone());
+            }
+            format!("{}", reaction)
+        } else {
+            let molecule = parser::parse_single_molecule(input)?;
+            format!("{}", Formula(&canonical(molecule)))
+        };
+        match format {
+            Format::Text => println!("{}", normalized),
+            Format::Json => {
+                println!("{}", Json::object(vec!(("normalized", Json::String(normalized)))))
+            },
+            Format::Csv | Format::Tsv => {
+                output::print_table(format, &["normalized"], &[vec!(normalized)])
+            },
+        }
+        Ok(())
+    }
+}
+
+fn diff_cmd(args: &[String], format: Format) -> CTResult<()> {
+    if args.len() < 2 {
+        Err(CTError::new(UsageError, "Missing formulas, diff needs two of them."))
+    } else if args.len() > 2 {
+        Err(CTError::new(UsageError, "Too many arguments."))
+    } else {
+        let before = parser::parse_single_molecule(args[0].as_str())?;
+        let after = parser::parse_single_molecule(args[1].as_str()).map_err(|e| {
+            e.shift(args[0].len() + 1)
+        })?;
+        let (lost, gained) = arith::diff(&before, &after);
+        let canonical = |molecule: Molecule| {
+            let order = canon::default_order(&molecule);
+            format!("{}", Formula(&canon::canonicalize(molecule, order)))
+        };
+        let (lost, gained) = (canonical(lost), canonical(gained));
+        match format {
+            Format::Text => {
+                let (first, second) = (args[0].as_str(), args[1].as_str());
+                // the equation is written so that the right hand side is never negative
+                match (lost.is_empty(), gained.is_empty()) {
+                    (true, true) => println!("{} = {}", first, second),
+                    (false, true) => println!("{} - {} = {}", first, second, lost),
+                    (true, false) => println!("{} - {} = {}", second, first, gained),
+                    (false, false) => println!("{} - {} = {} - {}", first, second, lost, gained),
+                }
+            },
+            Format::Json => {
+                println!("{}", Json::object(vec!(
+                    ("lost", Json::String(lost)),
+                    ("gained", Json::String(gained)),
+                    ("same", Json::Bool(arith::same_formula(&before, &after))),
+                )))
+            },
+            Format::Csv | Format::Tsv => {
+                output::print_table(format, &["lost", "gained"], &[vec!(lost, gained)])
+            },
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+fn calc_cmd(args: &[String], db_path: &Path, compounds_path: &Path, format: Format)
+            -> CTResult<()> {
+    if args.is_empty() {
+        Err(CTError::new(UsageError, "Missing expression."))
+    } else if args.len() > 1 {
+        Err(CTError::new(UsageError, "Too many arguments."))
+    } else {
+        let mut database = ElemDatabase::open(db_path)?;
+        let compounds = CompoundDatabase::open(compounds_path).ok();
+        let result = calc::evaluate(args[0].as_str(), |formula| {
+            calc::molar_mass(&mut database, compounds.as_ref(), formula)
+        })?;
+        match format {
+            Format::Text => println!("{}", result),
+            Format::Json => {
+                println!("{}", Json::object(vec!(("value", Json::Number(result.value)),
+                                                 ("unit", Json::String(result.unit_name())))))
+            },
+            Format::Csv | Format::Tsv => {
+                let rows = vec!(vec!(format!("{}", result.value), result.unit_name()));
+                output::print_table(format, &["value", "unit"], &rows)
+            },
+        }
+        Ok(())
+    }
+}
+
+fn repl_cmd(args: &[String], db_path: &Path, compounds_path: &Path) -> CTResult<()> {
+    if !args.is_empty() {
+        Err(CTError::new(UsageError, "Too many arguments."))
+    } else {
+        let database = ElemDatabase::open(db_path)?;
+        // the prompt is still useful without compound names, so a missing database is ignored
+        let compounds = CompoundDatabase::open(compounds_path).ok();
+        repl::Repl::new(database, compounds).run();
+        Ok(())
+    }
+}
+
+/// Runs the mass or balance command on each line of a file, or stdin if the source is '-'
+///
+/// Each non-empty line holds a single formula or reaction, and gives a single line of output.
+/// An error in one line is reported along with its line number, and does not stop the remaining
+/// lines from being processed. In JSON, the line number is the `line` field of the error object.
+fn batch_cmd(cmd: &str, args: &[String], source: &str, db_path: &Path, compounds_path: &Path,
+             format: Format) -> CTResult<()> {
+    if args.iter().any(|a| a.as_str() != "-") {
+        return Err(CTError::new(UsageError, "Too many arguments."));
+    }
+    let contents = read_batch_input(source)?;
+    // the databases are only opened once, and then reused for every line
+    let compounds = CompoundDatabase::open(compounds_path).ok();
+    let mut database = match ElemDatabase::open(db_path) {
+        Ok(database) => Some(database),
+        Err(e) if cmd == "mass" => return Err(e),
+        // balancing does not need the elements, only the lookup of names does
+        Err(_) => None,
+    };
+
+    if format == Format::Csv || format == Format::Tsv {
+        let header = if cmd == "mass" {
+            vec!("formula".to_string(), "molar_mass".to_string())
+        } else {
+            vec!("reaction".to_string(), "balanced".to_string())
+        };
+        println!("{}", output::format_row(format, &header));
+    }
+    for (i, line) in contents.lines().enumerate().filter(|&(_, l)| !l.trim().is_empty()) {
+        let line = line.trim();
+        let line = match (cmd, compounds.as_ref(), database.as_mut()) {
+            ("mass", Some(db), Some(elems)) => db.resolve_molecule(line, elems).0,
+            (_, Some(db), Some(elems)) => db.resolve_reaction(line, elems).0,
+            _ => line.to_string(),
+        };
+        let result = match (cmd, database.as_mut()) {
+            ("mass", Some(database)) => batch_mass_line(line.as_str(), database, format),
+            _ => batch_balance_line(line.as_str(), format),
+        };
+        // a failed line does not stop the batch, but the exit status still reports the failure
+        if let Err(ref e) = result {
+            set_exit_status(e.kind.exit_code());
+        }
+        match result {
+            Err(ref e) if format == Format::Json => {
+                let mut json = e.to_json();
+                if let Json::Object(ref mut pairs) = json {
+                    pairs.insert(0, ("line".to_string(), Json::Number((i + 1) as f64)));
+                }
+                eprintln!("{}", json);
+            },
+            Err(e) => {
+                let e = CTError { desc: format!("Line {}: {}", i + 1, e.desc), ..e };
+                e.print(Some(line.as_str()));
+            },
+            Ok(()) => (),
+        }
+    }
+    Ok(())
+}
+
+fn batch_mass_line(line: &str, database: &mut ElemDatabase, format: Format) -> CTResult<()> {
+    let parsed = match parser::parse_single_molecule(line) {
+        Ok(parsed) => parsed,
+        Err(e) => return Err(suggest::add_help(e, line, database)),
+    };
+    let molecule = elem::strip_charge(parsed.clone());
+    let data = match database.get_data(&molecule) {
+        Ok(data) => data,
+        Err(e) => return Err(suggest::add_help(e, line, database)),
+    };
+    let total = mass::molar_mass(&data, &molecule);
+    match format {
+        Format::Text => println!("{}: {}", line, total),
+        Format::Json => println!("{}", mass::to_json(&data, &parsed)),
+        Format::Csv | Format::Tsv => {
+            println!("{}", output::format_row(format, &[line.to_string(), format!("{}", total)]))
+        },
+    }
+    Ok(())
+}
+
+fn batch_balance_line(line: &str, format: Format) -> CTResult<()> {
+    let mut parser = Parser::new(line);
+    let mut reaction = parser.parse_reaction()?;
+    reaction.balance()?;
+    match format {
+        Format::Text => println!("{}", balance::format_balanced(&reaction)),
+        Format::Json => println!("{}", balance::to_json(&reaction)),
+        Format::Csv | Format::Tsv => {
+            let balanced = balance::format_balanced(&reaction);
+            println!("{}", output::format_row(format, &[line.to_string(), balanced]))
+        },
+    }
+    Ok(())
+}
+
+/// Adds suggestions for mistyped element symbols to an error in the input
+///
+/// The element database is only opened here, so commands which do not otherwise need it can still
+/// make suggestions. If it can not be opened, the error is returned untouched.
+fn suggest_symbols(e: CTError, input: &str, db_path: &Path) -> CTError {
+    match ElemDatabase::open(db_path) {
+        Ok(mut database) => suggest::add_help(e, input, &mut database),
+        Err(_) => e,
+    }
+}
+
+/// Reads the whole input for batch mode, either from a file or from stdin if the source is '-'
+fn read_batch_input(source: &str) -> CTResult<String> {
+    let contents = if source == "-" {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents).map(|_| contents)
+    } else {
+        fs::read_to_string(source)
+    };
+    contents.map_err(|e| {
+        CTError::new(InputError, format!("Could not read input file: {:?}", source)).with_source(e)
+    })
+}
+
+/// Replaces any compound names in the input of a command by their formulas
+///
+/// When writing text, each replaced name is echoed along with its formula, so the user can see
+/// what is being worked on. If either the compound or the element database can not be opened,
+/// the input is returned untouched.
+fn resolve_names(cmd: &str, input: &str, db_path: &Path, compounds_path: &Path, format: Format)
+                 -> String {
+    let (database, mut elems) = match (CompoundDatabase::open(compounds_path),
+                                       ElemDatabase::open(db_path)) {
+        (Ok(database), Ok(elems)) => (database, elems),
+        _ => return input.to_string(),
+    };
+    let (resolved, replaced) = match cmd {
+        "mass" | "solution" | "gas" | "diff" => database.resolve_molecule(input, &mut elems),
+        "balance" | "thermo" | "normalize" => database.resolve_reaction(input, &mut elems),
+        _ => return input.to_string(),
+    };
+    if format == Format::Text {
+        for (name, formula) in replaced.iter() {
+            println!("{} = {}", name, formula);
+        }
+    }
+    resolved
+}
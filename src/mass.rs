@@ -1,14 +1,29 @@
-use elem::Molecule;
-use database::ElemData;
+use crate::canon;
+use crate::elem;
+use crate::elem::{Molecule, Formula};
+use crate::database::ElemData;
+use crate::json::Json;
+
+/// The header for the table made by `to_rows`
+pub static TABLE_HEADER: [&str; 7] =
+    ["symbol", "count", "molar_mass", "name", "atomic_number", "mass", "percent"];
+
+/// Calculates the total molar mass of a molecule
+///
+/// The data for each element must be in the same order as the elements in the molecule, which is
+/// what `ElemDatabase::get_data` returns.
+pub fn molar_mass(elem_data: &[ElemData], molecule: &Molecule) -> f64 {
+    elem_data.iter()
+             .zip(molecule.iter())
+             .fold(0f64, |t, (data, elem)| t + data.mass * elem.coef as f64)
+}
 
 /// Takes a parsed checmical formula containing a single molecule, and pretty print the mass
 ///
 /// The function will print the molar mass (and some other data) for each element
 /// in the given molecule, as well as the total molar mass.
-pub fn pretty_print_data(elem_data: &Vec<ElemData>, molecule: &Molecule) {
-    let total = elem_data.iter()
-                         .zip(molecule.iter())
-                         .fold(0f64, |t, (ref data, ref elem)| t + data.mass * elem.coef as f64);
+pub fn pretty_print_data(elem_data: &[ElemData], molecule: &Molecule) {
+    let total = molar_mass(elem_data, molecule);
 
     println!("abbrv.     amt.          M             name          Z");
     println!("------------------------------------------------------");
@@ -23,4 +38,91 @@ pub fn pretty_print_data(elem_data: &Vec<ElemData>, molecule: &Molecule) {
                  data.atomic_num);
     }
     println!("Total: {}", total);
+}
+
+/// Makes the rows of a table with the same data as printed by `pretty_print_data`
+///
+/// Besides the data for each element, the mass it contributes to the molecule is included, both
+/// in grams per mole and as a percentage of the total.
+pub fn to_rows(elem_data: &[ElemData], molecule: &Molecule) -> Vec<Vec<String>> {
+    let total = molar_mass(elem_data, molecule);
+    elem_data.iter().zip(molecule.iter()).map(|(data, elem)| {
+        let mass = data.mass * elem.coef as f64;
+        vec!(data.short_name.clone(),
+             format!("{}", elem.coef),
+             format!("{}", data.mass),
+             data.long_name.clone(),
+             format!("{}", data.atomic_num),
+             format!("{}", mass),
+             format!("{}", mass / total * 100.0))
+    }).collect()
+}
+
+/// Makes a JSON object with the same data as printed by `pretty_print_data`
+///
+/// Besides the data for each element, the mass it contributes to the molecule is included. Unlike
+/// the other functions, the molecule is given with its charge, so the formula can keep it. The
+/// formula is written in the order from `canon::default_order`.
+pub fn to_json(elem_data: &[ElemData], charged: &Molecule) -> Json {
+    let formula = canon::canonicalize(charged.clone(), canon::default_order(charged));
+    let molecule = &elem::strip_charge(charged.clone());
+    let elements = elem_data.iter().zip(molecule.iter()).map(|(data, elem)| {
+        Json::object(vec!(
+            ("symbol", Json::string(&data.short_name)),
+            ("count", Json::Number(elem.coef as f64)),
+            ("molar_mass", Json::Number(data.mass)),
+            ("name", Json::string(&data.long_name)),
+            ("atomic_number", Json::Number(data.atomic_num as f64)),
+            ("mass", Json::Number(data.mass * elem.coef as f64)),
+        ))
+    }).collect();
+    Json::object(vec!(
+        ("formula", Json::string(Formula(&formula))),
+        ("elements", Json::Array(elements)),
+        ("total", Json::Number(molar_mass(elem_data, molecule))),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::parse_single_molecule;
+
+    fn elem_data(short_name: &str, long_name: &str, mass: f64, atomic_num: u16) -> ElemData {
+        ElemData { short_name: short_name.to_string(), long_name: long_name.to_string(), mass,
+                   atomic_num }
+    }
+
+    fn sulfate_data() -> Vec<ElemData> {
+        vec!(elem_data("O", "Oxygen", 16.0, 8), elem_data("S", "Sulfur", 32.0, 16))
+    }
+
+    #[test]
+    fn json() {
+        let json = to_json(&sulfate_data(), &parse_single_molecule("SO4^2-").unwrap());
+        assert_eq!(json.to_string(),
+                   "{\"formula\":\"SO4^2-\",\"elements\":[\
+                    {\"symbol\":\"O\",\"count\":4,\"molar_mass\":16,\"name\":\"Oxygen\",\
+                    \"atomic_number\":8,\"mass\":64},\
+                    {\"symbol\":\"S\",\"count\":1,\"molar_mass\":32,\"name\":\"Sulfur\",\
+                    \"atomic_number\":16,\"mass\":32}],\"total\":96}");
+    }
+
+    #[test]
+    fn json_formula_order() {
+        let data = vec!(elem_data("C", "Carbon", 12.0, 6), elem_data("H", "Hydrogen", 1.0, 1),
+                        elem_data("O", "Oxygen", 16.0, 8));
+        let json = to_json(&data, &parse_single_molecule("CH3COOH").unwrap()).to_string();
+        assert!(json.starts_with("{\"formula\":\"C2H4O2\","), "{}", json);
+    }
+
+    #[test]
+    fn rows() {
+        let molecule = elem::strip_charge(parse_single_molecule("SO4^2-").unwrap());
+        let rows = to_rows(&sulfate_data(), &molecule);
+        assert_eq!(rows, vec!(
+            vec!("O", "4", "16", "Oxygen", "8", "64", "66.66666666666666"),
+            vec!("S", "1", "32", "Sulfur", "16", "32", "33.33333333333333"),
+        ));
+    }
 }
\ No newline at end of file
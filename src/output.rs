@@ -0,0 +1,123 @@
+//! The output formats chemtool can write its results in.
+
+use crate::error::{CTResult, CTError};
+use crate::error::CTErrorKind::UsageError;
+
+/// The header for tables of quantities, as made by `quantity_rows`
+pub static QUANTITY_HEADER: [&str; 3] = ["quantity", "value", "unit"];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    /// Human readable text, e.g. the tables printed by the mass command
+    Text,
+    /// A single JSON object per result
+    Json,
+    /// A table of comma separated values, with a header line
+    Csv,
+    /// A table of tab separated values, with a header line
+    Tsv,
+}
+
+impl Format {
+    /// Finds the format with the given name, as given to the --format option
+    pub fn from_name(name: &str) -> CTResult<Format> {
+        match name {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err(CTError::new(UsageError, format!("Unknown output format: {:?}", name))),
+        }
+    }
+}
+
+/// Prints a table in the given format, which must be either Csv or Tsv
+pub fn print_table(format: Format, header: &[&str], rows: &[Vec<String>]) {
+    print!("{}", format_table(format, header, rows));
+}
+
+/// Formats a table as comma (for Csv) or tab (for Tsv) separated values
+///
+/// The header is written as the first line, followed by a line for each row.
+pub fn format_table(format: Format, header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut out = String::new();
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    for row in Some(&header).into_iter().chain(rows.iter()) {
+        out.push_str(format_row(format, row).as_str());
+        out.push('\n');
+    }
+    out
+}
+
+/// Formats a single row of a table as comma (for Csv) or tab (for Tsv) separated values
+///
+/// The returned string does not end with a newline.
+pub fn format_row(format: Format, row: &[String]) -> String {
+    let sep = if format == Format::Tsv { "\t" } else { "," };
+    let fields: Vec<String> = row.iter().map(|f| escape_field(format, f.as_str())).collect();
+    fields.join(sep)
+}
+
+/// Makes the rows of a table of quantities, leaving out those which are not known
+///
+/// Each row holds the name, value and unit of a quantity.
+pub fn quantity_rows(quantities: &[(&str, Option<f64>, &str)]) -> Vec<Vec<String>> {
+    quantities.iter()
+              .filter_map(|&(name, value, unit)| {
+                  value.map(|v| vec!(name.to_string(), format!("{}", v), unit.to_string()))
+              })
+              .collect()
+}
+
+fn escape_field(format: Format, field: &str) -> String {
+    if format == Format::Tsv {
+        // TSV has no way of quoting, so we just replace the problematic characters
+        field.chars().map(|ch| if matches!(ch, '\t' | '\n' | '\r') { ' ' } else { ch }).collect()
+    } else if field.chars().any(|ch| matches!(ch, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", field.replace("\"", "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter().map(|r| r.iter().map(|f| f.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn csv() {
+        let result = format_table(Format::Csv, &["a", "b"], &rows(&[&["1", "2"], &["3", "4"]]));
+        assert_eq!(result, "a,b\n1,2\n3,4\n");
+    }
+
+    #[test]
+    fn csv_quoting() {
+        let result = format_table(Format::Csv, &["a"], &rows(&[&["x, \"y\""]]));
+        assert_eq!(result, "a\n\"x, \"\"y\"\"\"\n");
+        let result = format_table(Format::Csv, &["a"], &rows(&[&["x\r\ny"]]));
+        assert_eq!(result, "a\n\"x\r\ny\"\n");
+    }
+
+    #[test]
+    fn tsv() {
+        let result = format_table(Format::Tsv, &["a", "b"], &rows(&[&["1\t2", "3"]]));
+        assert_eq!(result, "a\tb\n1 2\t3\n");
+        let result = format_table(Format::Tsv, &["a"], &rows(&[&["1\r\n2"]]));
+        assert_eq!(result, "a\n1  2\n");
+    }
+
+    #[test]
+    fn quantities() {
+        let result = quantity_rows(&[("mass", Some(1.5), "g"), ("volume", None, "L")]);
+        assert_eq!(result, rows(&[&["mass", "1.5", "g"]]));
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(Format::from_name("xml").is_err());
+    }
+}
@@ -5,71 +5,161 @@
 //!
 //! The parser works with the following grammar:
 //! R = (R)eaction
+//! W = arro(W), along with the conditions of the reaction
 //! S = one (S)ide of a reaction
+//! N = stoichiometric coefficie(N)t of a species
+//! T = physical s(T)ate of a molecule
 //! M = (M)olecule
+//! A = (A)dduct, e.g. the water of crystallisation in CuSO4·5H2O
+//! Q = Charge of a molecule, e.g. the 2- in SO4^2-
 //! P = Combination of a (P)eriodic element and maybe a coefficient
 //! E = Periodic (E)lement
 //! C = (C)oefficient
 //!
-//! R --> S -> S
-//! S --> M + S
-//!    |  M
+//! R --> S W S
+//! W --> -> | -> [<text>, <text>, ...]
+//! S --> NMT + S
+//!    |  NMT
+//! N --> <number>
+//!    |  <nothing>
+//! T --> (s) | (l) | (g) | (aq)
+//!    |  <nothing>
 //! M --> PM
+//!    |  P·A
+//!    |  PQ
 //!    |  P
+//! A --> CM
+//!    |  M
+//! Q --> ^C+ | ^C- | ^+ | ^-
 //! P --> EC
 //!    |  E
 //! E --> <text>
 //!    |  (M)
 //! C --> <number>
-
-use std::str::CharRange;
-use elem::{PerElem, Molecule};
-use error::{CTResult, CTError};
-use error::CTErrorKind::InputError;
+//!
+//! Where the adduct dot `·` may also be written as `.` or `*`, and the arrow `->` may also be
+//! written as `→`, or as `<=>`, `<->` or `⇌` for a reaction in equilibrium. The arrow `->` in W
+//! stands for any of these. Coefficients may be written with subscript digits, e.g. `H₂O`, and
+//! charges with superscripts, e.g. `SO₄²⁻`.
+//!
+//! A charge is stored as a PerElem named `+` or `-`, with the size of the charge as its
+//! coefficient.
+
+use std::collections::BTreeMap;
+use crate::elem;
+use crate::elem::{PerElem, Molecule, State, Species, Reaction, Arrow};
+use crate::error::{CTResult, CTError, Label};
+use crate::error::CTErrorKind::InputError;
+
+static SUBSCRIPT_DIGITS: &str = "₀₁₂₃₄₅₆₇₈₉";
+static SUPERSCRIPT_DIGITS: &str = "⁰¹²³⁴⁵⁶⁷⁸⁹";
+
+static ARROWS: [(&str, Arrow); 5] = [
+    ("->", Arrow::Forward),
+    ("→", Arrow::Forward),
+    ("<=>", Arrow::Equilibrium),
+    ("<->", Arrow::Equilibrium),
+    ("⇌", Arrow::Equilibrium),
+];
+
+static STATES: [(&str, State); 4] = [
+    ("(s)", State::Solid),
+    ("(l)", State::Liquid),
+    ("(g)", State::Gas),
+    ("(aq)", State::Aqueous),
+];
 
 pub struct Parser {
     pos: usize,
     input: String,
     paren_level: u32,
+    errors: Vec<CTError>,
 }
 
 impl Parser {
     /// Create a new Parser with the given input string
     pub fn new(input: &str) -> Parser {
-        Parser { pos: 0, input: String::from_str(input), paren_level: 0 }
+        Parser {
+            pos: 0,
+            input: String::from(input),
+            paren_level: 0,
+            errors: Vec::new(),
+        }
     }
 
     /// Returns true if there is nothing left to parse
     ///
     /// If there is nothing but whitespace left, then we are done with the parsing.
     pub fn is_done(&self) -> bool {
-        self.input.chars().skip(self.pos).all(|ch| ch.is_whitespace())
+        self.input[self.pos..].chars().all(|ch| ch.is_whitespace())
     }
 
     /// Parses the formula for a chemical reaction
     ///
     /// This runs through the full grammar and parses a reaction conforming to it.
-    /// The two sides of the reaction are represented as Vecs of Molecules (which
-    /// are themselves Vecs of PerElems).
-    pub fn parse_reaction(&mut self) -> CTResult<(Vec<Molecule>, Vec<Molecule>)> {
-        let lhs = try!(self.parse_side());
+    /// Each side of the reaction is a Vec of Species, which hold the molecule along with its
+    /// coefficient and state.
+    ///
+    /// A molecule with a syntax error does not stop the parsing, instead the parser skips to the
+    /// next molecule, so every error in the reaction can be reported at once.
+    pub fn parse_reaction(&mut self) -> CTResult<Reaction> {
+        let lhs = self.parse_side_recovering();
         self.consume_whitespace();
 
-        // we do not care if some of the consumes are not called, since this is
-        // an error anyway, and will abort the parsing
-        if self.pos + 2 >= self.input.len() || self.consume_char() != '-' ||
-                                               self.consume_char() != '>' {
-            return Err(CTError {
-                kind: InputError,
-                desc: "Missing arrow (->) in chemical reaction".to_string(),
-                pos: Some((self.pos - 2, 1))
-            });
-        }
+        // there must also be something after the arrow, since the right side can not be empty
+        let rest = &self.input[self.pos..];
+        let arrow = match ARROWS.iter().find(|&&(text, _)| rest.starts_with(text)) {
+            Some(&(text, arrow)) if self.pos + text.len() < self.input.len() => {
+                self.pos += text.len();
+                arrow
+            },
+            _ => {
+                // without the arrow, there is no telling where the right side would start
+                self.errors.push(CTError::new(InputError, "Missing arrow (->) in chemical reaction")
+                    .with_pos((self.pos, 1)));
+                return Err(self.take_errors());
+            },
+        };
+        let conditions = match self.parse_conditions() {
+            Ok(conditions) => conditions,
+            Err(e) => {
+                self.errors.push(e);
+                return Err(self.take_errors());
+            },
+        };
         self.consume_whitespace();
 
-        let rhs = try!(self.parse_side());
+        let rhs = self.parse_side_recovering();
+
+        if self.errors.is_empty() {
+            Ok(Reaction { reactants: lhs, products: rhs, arrow, conditions })
+        } else {
+            Err(self.take_errors())
+        }
+    }
 
-        Ok((lhs, rhs))
+    /// Parses the conditions in square brackets right after the arrow, e.g. `[heat, Pt]`
+    ///
+    /// The conditions are kept as the text which was written, and there are none if there are no
+    /// brackets.
+    fn parse_conditions(&mut self) -> CTResult<Vec<String>> {
+        if self.eof() || self.peek_char() != '[' {
+            return Ok(Vec::new());
+        }
+        match self.input[self.pos..].find(']') {
+            Some(len) => {
+                let text = &self.input[self.pos + 1..self.pos + len];
+                let conditions = text.split(',')
+                                     .map(|c| c.trim().to_string())
+                                     .filter(|c| !c.is_empty())
+                                     .collect();
+                self.pos += len + 1;
+                Ok(conditions)
+            },
+            None => Err(CTError::new(InputError,
+                                     "Missing closing bracket after the reaction conditions")
+                .with_pos((self.pos, 1))),
+        }
     }
 
     /// Parses a single side in a chemical reaction
@@ -77,20 +167,86 @@ impl Parser {
     /// This runs through a subset of the grammar in order to parse a single side
     /// of a checmical reaction. Note that there still be more to parse after a
     /// call to this function.
-    pub fn parse_side(&mut self) -> CTResult<Vec<Molecule>> {
+    pub fn parse_side(&mut self) -> CTResult<Vec<Species>> {
+        let side = self.parse_side_recovering();
+        if self.errors.is_empty() {
+            Ok(side)
+        } else {
+            Err(self.take_errors())
+        }
+    }
+
+    /// Parses a single side, keeping any errors in the molecules for later
+    ///
+    /// After an error, the rest of the erroneous molecule is skipped, and the parsing goes on
+    /// from the next molecule.
+    fn parse_side_recovering(&mut self) -> Vec<Species> {
         let mut out = Vec::new();
-        let molecule = try!(self.parse_molecule());
-        out.push(molecule);
-        self.consume_whitespace();
+        loop {
+            let start = self.pos;
+            let coef = self.parse_species_coefficient();
+            match self.parse_molecule() {
+                Ok(molecule) => {
+                    let state = self.parse_state();
+                    let pos = (start, self.pos - start);
+                    out.push(Species { molecule, coef, state, pos });
+                },
+                Err(e) => {
+                    self.errors.push(e);
+                    self.skip_molecule(start);
+                },
+            }
+            self.consume_whitespace();
 
-        if !self.eof() && self.peek_char() == '+' {
+            if !self.eof() && self.peek_char() == '+' {
+                self.consume_char();
+                self.consume_whitespace();
+            } else {
+                return out;
+            }
+        }
+    }
+
+    /// Parses the coefficient written before a species, e.g. the 2 in `2 H2O`, if there is one
+    ///
+    /// The coefficient is only taken if it is followed by a molecule, so a stray number is left
+    /// for `parse_molecule` to report.
+    fn parse_species_coefficient(&mut self) -> u32 {
+        let digits = self.input[self.pos..].chars().take_while(|ch| ch.is_ascii_digit()).count();
+        let rest = self.input[self.pos + digits..].trim_start();
+        let before_molecule = rest.starts_with(|ch: char| ch.is_uppercase() || ch == '(');
+        match self.input[self.pos..self.pos + digits].parse::<u32>() {
+            Ok(coef) if before_molecule => {
+                self.pos += digits;
+                self.consume_whitespace();
+                coef
+            },
+            _ => 1,
+        }
+    }
+
+    /// Skips past the molecule starting at the given position, up to the `+` or arrow after it
+    fn skip_molecule(&mut self, start: usize) {
+        // the parsing may have stopped at a `+` or arrow, which must not be skipped
+        self.pos = start;
+        self.paren_level = 0;
+        while !self.eof() {
+            let at_boundary = {
+                let rest = &self.input[self.pos..];
+                ARROWS.iter().any(|&(arrow, _)| rest.starts_with(arrow)) ||
+                    (rest.starts_with("+") && !ends_in_caret(&self.input[..self.pos]))
+            };
+            if at_boundary {
+                return;
+            }
             self.consume_char();
-            self.consume_whitespace();
-            let mut rest = try!(self.parse_side());
-            out.append(&mut rest);
         }
+    }
 
-        Ok(out)
+    /// Combines the errors found so far into a single error, and forgets about them
+    fn take_errors(&mut self) -> CTError {
+        let errors = std::mem::take(&mut self.errors);
+        CTError::combine(errors)
     }
 
     /// Parses a single molecule
@@ -100,38 +256,77 @@ impl Parser {
     /// call to this function, and no whitespace is allowed in a molecule.
     pub fn parse_molecule(&mut self) -> CTResult<Molecule> {
         let mut out = Vec::new();
-        let mut per = try!(self.parse_periodic());
+        let mut per = self.parse_periodic()?;
         out.append(&mut per);
 
         // TODO: Make this cleaner
-        if !self.eof() && (self.peek_char().is_alphabetic() || self.peek_char() == '(') {
-            let mut molecule = try!(self.parse_molecule());
+        if !self.eof() && (self.peek_char().is_alphabetic() ||
+                           (self.peek_char() == '(' && !self.on_state())) {
+            let mut molecule = self.parse_molecule()?;
             out.append(&mut molecule);
         }
+        if !self.eof() && is_adduct_dot(self.peek_char()) {
+            let mut adduct = self.parse_adduct()?;
+            out.append(&mut adduct);
+        }
+        if !self.eof() && self.on_charge() {
+            let charge = self.parse_charge()?;
+            out.push(charge);
+        }
         if !self.eof() && self.peek_char() == ')' && self.paren_level == 0 {
-            Err(CTError {
-                kind: InputError,
-                desc: "Missing opening parentheses".to_string(),
-                pos: Some((self.pos, 1))
-            })
+            Err(CTError::new(InputError, "Missing opening parentheses")
+                .with_pos((self.pos, 1)))
         } else if !self.eof() && !self.on_legal_char() {
-            Err(CTError {
-                kind: InputError,
-                desc: "Unexpected character".to_string(),
-                pos: Some((self.pos, 1))
-            })
+            Err(CTError::new(InputError, "Unexpected character")
+                .with_pos((self.pos, 1)))
         } else {
+            check_totals(&out)?;
             Ok(out)
         }
     }
 
+    /// Parses the physical state following a molecule, e.g. `(aq)`, if there is one
+    pub fn parse_state(&mut self) -> Option<State> {
+        for &(text, state) in STATES.iter() {
+            if self.input[self.pos..].starts_with(text) {
+                self.pos += text.len();
+                return Some(state);
+            }
+        }
+        None
+    }
+
+    fn parse_adduct(&mut self) -> CTResult<Vec<PerElem>> {
+        // skip past the dot
+        self.consume_char();
+        let mult_pos = self.pos;
+        let mult = if !self.eof() && coef_digit(self.peek_char()).is_some() {
+            self.parse_coefficient()?
+        } else {
+            1
+        };
+        let mult_len = self.pos - mult_pos;
+        let mut molecule = self.parse_molecule()?;
+        for e in molecule.iter_mut() {
+            e.coef = e.coef.checked_mul(mult).ok_or_else(|| {
+                CTError::new(InputError, "The coefficient of the adduct is too large")
+                    .with_pos((mult_pos, mult_len))
+            })?;
+        }
+        Ok(molecule)
+    }
+
     fn parse_periodic(&mut self) -> CTResult<Vec<PerElem>> {
-        let mut elem = try!(self.parse_element());
+        let mut elem = self.parse_element()?;
 
-        if !self.eof() && self.peek_char().is_numeric() {
-            let coef = try!(self.parse_coefficient());
+        if !self.eof() && coef_digit(self.peek_char()).is_some() {
+            let coef_pos = self.pos;
+            let coef = self.parse_coefficient()?;
             for e in elem.iter_mut() {
-                e.coef *= coef;
+                e.coef = e.coef.checked_mul(coef).ok_or_else(|| {
+                    CTError::new(InputError, "The coefficient is too large")
+                        .with_pos((coef_pos, self.pos - coef_pos))
+                })?;
             }
         }
 
@@ -140,24 +335,23 @@ impl Parser {
 
     fn parse_element(&mut self) -> CTResult<Vec<PerElem>> {
         if self.eof() {
-            return Err(CTError {
-                kind: InputError,
-                desc: "Found no periodic element".to_string(),
-                pos: Some((self.pos, 1))
-            });
+            return Err(CTError::new(InputError, "Found no periodic element")
+                .with_pos((self.pos, 1)));
         }
         // we store the current position here, so the consumes don't mess it up
         let start_pos = self.pos;
         let first = self.consume_char();
         if first == '(' {
             self.paren_level += 1;
-            let molecule = try!(self.parse_molecule());
+            let molecule = self.parse_molecule()?;
             if self.eof() || self.consume_char() != ')' {
-                Err(CTError {
-                    kind: InputError,
-                    desc: "Missing closing parentheses".to_string(),
-                    pos: Some((self.pos - 1, 1))
-                })
+                let pos = (self.pos - 1, 1);
+                Err(CTError::new(InputError, "Missing closing parentheses")
+                    .with_pos(pos)
+                    .with_labels(vec!(
+                        Label { pos: (start_pos, 1), text: "opened here".to_string() },
+                        Label { pos, text: "expected closing parenthesis here".to_string() },
+                    )))
             } else {
                 self.paren_level -= 1;
                 Ok(molecule)
@@ -165,39 +359,61 @@ impl Parser {
         } else if first.is_uppercase() {
             let mut name = String::new();
             name.push(first);
-            name.push_str(self.consume_while(|ch| ch.is_lowercase()).as_slice());
+            name.push_str(self.consume_while(|ch| ch.is_lowercase()).as_str());
             let len = name.len();
-            Ok(vec!(PerElem { name: name, coef: 1, pos: start_pos, len: len }))
+            Ok(vec!(PerElem { name, coef: 1, pos: start_pos, len }))
         } else {
-            Err(CTError {
-                kind: InputError,
-                desc: "Missing uppercase letter at the beginning of the element".to_string(),
-                pos: Some((self.pos - 1, 1))
-            })
+            Err(CTError::new(InputError, "Missing uppercase letter at the beginning of the element")
+                .with_pos((self.pos - 1, 1)))
         }
     }
 
     fn parse_coefficient(&mut self) -> CTResult<u32> {
         let start_pos = self.pos;
-        let num_str = self.consume_while(|ch| ch.is_numeric());
-        if let Ok(num) = num_str.parse::<u32>() {
+        let digits = self.consume_while(|ch| coef_digit(ch).is_some());
+        if let Some(num) = digits_value(digits.as_str(), coef_digit) {
             Ok(num)
         } else {
-            Err(CTError {
-                kind: InputError,
-                desc: "Could not parse coefficient".to_string(),
-                pos: Some((start_pos, num_str.len()))
-            })
+            Err(CTError::new(InputError, "Could not parse coefficient")
+                .with_pos((start_pos, self.pos - start_pos)))
+        }
+    }
+
+    fn parse_charge(&mut self) -> CTResult<PerElem> {
+        let start_pos = self.pos;
+        // a charge is either written in ASCII after a caret, e.g. ^2-, or with superscripts
+        let ascii = self.peek_char() == '^';
+        if ascii {
+            self.consume_char();
         }
+        let digit: fn(char) -> Option<u32> = if ascii { ascii_digit } else { superscript_digit };
+        let digits = self.consume_while(|ch| digit(ch).is_some());
+        let size = if digits.is_empty() {
+            1
+        } else if let Some(size) = digits_value(digits.as_str(), digit) {
+            size
+        } else {
+            return Err(CTError::new(InputError, "Could not parse charge")
+                .with_pos((start_pos, self.pos - start_pos)));
+        };
+        let sign = match (ascii, if self.eof() { ' ' } else { self.peek_char() }) {
+            (true, '+') | (false, '⁺') => "+",
+            (true, '-') | (false, '⁻') => "-",
+            _ => return Err(CTError::new(InputError, "Missing sign (+ or -) of the charge")
+                .with_pos((self.pos, 1))),
+        };
+        self.consume_char();
+        let len = self.pos - start_pos;
+        Ok(PerElem { name: sign.to_string(), coef: size, pos: start_pos, len })
     }
 
     fn peek_char(&self) -> char {
-        self.input.char_at(self.pos)
+        self.input[self.pos..].chars().next().unwrap()
     }
 
     fn consume_char(&mut self) -> char {
-        let CharRange { ch, next } = self.input.char_range_at(self.pos);
-        self.pos = next;
+        let ch = self.peek_char();
+        self.pos += ch.len_utf8();
         ch
     }
 
@@ -213,30 +429,183 @@ impl Parser {
         self.consume_while(|ch| ch.is_whitespace());
     }
 
-    fn eof(&mut self) -> bool {
+    fn eof(&self) -> bool {
         self.pos >= self.input.len()
     }
 
+    fn on_state(&self) -> bool {
+        STATES.iter().any(|&(text, _)| self.input[self.pos..].starts_with(text))
+    }
+
+    fn on_charge(&self) -> bool {
+        match self.peek_char() {
+            '^' | '⁺' | '⁻' => true,
+            ch => superscript_digit(ch).is_some(),
+        }
+    }
+
     fn on_legal_char(&self) -> bool {
         match self.peek_char() {
             ch if ch.is_alphanumeric() => true,
-            '+' | '-' | '>' | '(' | ')' | ' ' => true,
+            '+' | '-' | '>' | '<' | '=' | '(' | ')' | '[' | ' ' | '→' | '⇌' => true,
+            ch if is_adduct_dot(ch) => true,
             _ => false,
         }
     }
 }
 
+/// Parses an input which must consist of a single molecule, and groups its elements
+pub fn parse_single_molecule(input: &str) -> CTResult<Molecule> {
+    let mut parser = Parser::new(input);
+    let molecule = parser.parse_molecule()?;
+    // the state of the molecule does not matter for its mass, so we just skip past it
+    parser.parse_state();
+    if !parser.is_done() {
+        // since there should be no whitespace in a molecule, the only way for parser to have
+        // returned sucess while not being done, is if there was some whitespace,
+        // followed by more (illegal) input
+        return Err(CTError::new(InputError, "A molecule must not contain whitespace"))
+    }
+    Ok(elem::group_elems(molecule))
+}
+
+/// Returns true if the input looks like a reaction rather than a single molecule
+pub fn is_reaction(input: &str) -> bool {
+    find_arrow(input).is_some()
+}
+
+/// Splits a reaction into its two sides at the arrow
+///
+/// The conditions after the arrow belong to neither side. If there is no arrow, the whole input
+/// is returned as a single side.
+pub fn split_sides(input: &str) -> Vec<&str> {
+    match find_arrow(input) {
+        Some((start, end)) => vec!(&input[..start], &input[end..]),
+        None => vec!(input),
+    }
+}
+
+/// Returns the arrow of a reaction along with its conditions, e.g. `->[heat]`
+///
+/// This is what goes between the sides returned by `split_sides`. The arrow is written the way
+/// `Arrow` displays it, e.g. `→` becomes `->`, while the conditions are kept as they were
+/// written. If there is no arrow, `->` is returned.
+pub fn arrow_text(input: &str) -> String {
+    match find_arrow(input) {
+        Some((start, end)) => {
+            let &(text, arrow) = ARROWS.iter().find(|&&(text, _)| input[start..].starts_with(text))
+                                              .unwrap();
+            format!("{}{}", arrow, &input[start + text.len()..end])
+        },
+        None => format!("{}", Arrow::Forward),
+    }
+}
+
+/// Finds the start and end of the first arrow in the input, including any conditions after it
+fn find_arrow(input: &str) -> Option<(usize, usize)> {
+    // the arrows overlap, e.g. `<->` contains `->`, so the one starting first is the right one
+    let (start, text) = ARROWS.iter()
+                              .filter_map(|&(text, _)| input.find(text).map(|i| (i, text)))
+                              .min_by_key(|&(i, _)| i)?;
+    let end = start + text.len();
+    match input[end..].find(']') {
+        Some(len) if input[end..].starts_with('[') => Some((start, end + len + 1)),
+        _ => Some((start, end)),
+    }
+}
+
+/// Splits one side of a reaction into its species at the `+` signs which separate them
+///
+/// A `+` which is part of a charge, e.g. in `Na^+`, does not separate two species.
+pub fn split_species(side: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    for (i, ch) in side.char_indices() {
+        if ch == '+' && !ends_in_caret(&side[..i]) {
+            out.push(&side[start..i]);
+            start = i + 1;
+        }
+    }
+    out.push(&side[start..]);
+    out
+}
+
+/// Checks that the total count of each element in a molecule fits in a coefficient
+///
+/// The elements are added up when the molecule is grouped, so e.g. `H4294967295H` must be
+/// rejected even though each of its coefficients fits.
+fn check_totals(molecule: &[PerElem]) -> CTResult<()> {
+    let mut totals = BTreeMap::new();
+    for e in molecule.iter() {
+        let total: &mut u32 = totals.entry(e.name.as_str()).or_insert(0);
+        *total = total.checked_add(e.coef).ok_or_else(|| {
+            let desc = format!("The total count of {} is too large", e.name);
+            CTError::new(InputError, desc).with_pos((e.pos, e.len))
+        })?;
+    }
+    Ok(())
+}
+
+/// Splits the state, e.g. `(aq)`, off the end of a species, if it has one
+pub fn split_state(species: &str) -> (&str, &str) {
+    for &(text, _) in STATES.iter() {
+        if let Some(rest) = species.strip_suffix(text) {
+            return (rest, text);
+        }
+    }
+    (species, "")
+}
+
+/// Returns true if a `+` following the input would be the sign of a charge, as in `Na^+`
+fn ends_in_caret(input: &str) -> bool {
+    input.trim_end_matches(|c: char| c.is_ascii_digit()).ends_with("^")
+}
+
+fn is_adduct_dot(ch: char) -> bool {
+    ch == '·' || ch == '.' || ch == '*'
+}
+
+fn ascii_digit(ch: char) -> Option<u32> {
+    ch.to_digit(10)
+}
+
+fn superscript_digit(ch: char) -> Option<u32> {
+    SUPERSCRIPT_DIGITS.chars().position(|d| d == ch).map(|i| i as u32)
+}
+
+/// Finds the value of a digit in a coefficient, which may be either an ASCII or subscript digit
+fn coef_digit(ch: char) -> Option<u32> {
+    ascii_digit(ch).or_else(|| SUBSCRIPT_DIGITS.chars().position(|d| d == ch).map(|i| i as u32))
+}
+
+/// Finds the value of a number written with the given kind of digits, if it fits in a u32
+fn digits_value(digits: &str, digit: fn(char) -> Option<u32>) -> Option<u32> {
+    let ascii: String = digits.chars().map(|ch| {
+        let value = digit(ch).unwrap();
+        (b'0' + value as u8) as char
+    }).collect();
+    ascii.parse::<u32>().ok()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use elem::PerElem;
+    use crate::elem::{PerElem, State};
+
+    fn molecules(side: Vec<Species>) -> Vec<Molecule> {
+        side.into_iter().map(|s| s.molecule).collect()
+    }
+
+    fn sides(reaction: Reaction) -> (Vec<Molecule>, Vec<Molecule>) {
+        (molecules(reaction.reactants), molecules(reaction.products))
+    }
 
     macro_rules! check_raw_result(
         ($raw:expr, $expected:expr) => (
             if let Ok(result) = $raw {
                 assert_eq!(result, $expected);
             } else {
-                panic!($raw);
+                panic!("{:?}", $raw);
             }
         )
     );
@@ -268,10 +637,72 @@ mod test {
         check_raw_result!(raw_result, expected);
     }
 
+    #[test]
+    fn adduct() {
+        let mut parser = Parser::new("CuSO4·5H2O");
+        let raw_result = parser.parse_molecule();
+        let expected = vec!(PerElem { name: "Cu".to_string(), coef: 1, pos: 0, len: 2 },
+                            PerElem { name: "S".to_string(), coef: 1, pos: 2, len: 1 },
+                            PerElem { name: "O".to_string(), coef: 4, pos: 3, len: 1 },
+                            PerElem { name: "H".to_string(), coef: 10, pos: 8, len: 1 },
+                            PerElem { name: "O".to_string(), coef: 5, pos: 10, len: 1 });
+        check_raw_result!(raw_result, expected);
+    }
+
+    #[test]
+    fn ascii_adduct() {
+        let mut parser = Parser::new("CaSO4.H2O");
+        let raw_result = parser.parse_molecule();
+        let expected = vec!(PerElem { name: "Ca".to_string(), coef: 1, pos: 0, len: 2 },
+                            PerElem { name: "S".to_string(), coef: 1, pos: 2, len: 1 },
+                            PerElem { name: "O".to_string(), coef: 4, pos: 3, len: 1 },
+                            PerElem { name: "H".to_string(), coef: 2, pos: 6, len: 1 },
+                            PerElem { name: "O".to_string(), coef: 1, pos: 8, len: 1 });
+        check_raw_result!(raw_result, expected);
+    }
+
+    #[test]
+    fn coefficient_overflow() {
+        let mut parser = Parser::new("(H2)4294967295");
+        let err = parser.parse_molecule().unwrap_err();
+        assert_eq!(err.desc, "The coefficient is too large");
+        assert_eq!(err.pos, Some((4, 10)));
+    }
+
+    #[test]
+    fn total_overflow() {
+        let err = parse_single_molecule("H4294967295H").unwrap_err();
+        assert_eq!(err.desc, "The total count of H is too large");
+        assert_eq!(err.pos, Some((11, 1)));
+        assert!(parse_single_molecule("(HH)2147483648").is_err());
+        assert!(Parser::new("H4294967295H -> H2").parse_reaction().is_err());
+    }
+
+    #[test]
+    fn split_states() {
+        assert_eq!(split_state("NaCl(aq)"), ("NaCl", "(aq)"));
+        assert_eq!(split_state("2 water (l)"), ("2 water ", "(l)"));
+        assert_eq!(split_state("CO2"), ("CO2", ""));
+    }
+
+    #[test]
+    fn dangling_adduct() {
+        let mut parser = Parser::new("CuSO4·");
+        assert!(parser.parse_molecule().is_err());
+    }
+
+    #[test]
+    fn adduct_overflow() {
+        let mut parser = Parser::new("CuSO4.5H4294967295O");
+        let err = parser.parse_molecule().unwrap_err();
+        assert_eq!(err.desc, "The coefficient of the adduct is too large");
+        assert_eq!(err.pos, Some((6, 1)));
+    }
+
     #[test]
     fn multiple_elems() {
         let mut parser = Parser::new("C + H");
-        let raw_result = parser.parse_side();
+        let raw_result = parser.parse_side().map(molecules);
         let expected = vec!(vec!(PerElem { name: "C".to_string(), coef: 1, pos: 0, len: 1 }),
                             vec!(PerElem { name: "H".to_string(), coef: 1, pos: 4, len: 1 }));
         check_raw_result!(raw_result, expected);
@@ -280,12 +711,38 @@ mod test {
     #[test]
     fn reaction() {
         let mut parser = Parser::new("C -> H");
-        let raw_result = parser.parse_reaction();
+        let raw_result = parser.parse_reaction().map(sides);
         let expected = (vec!(vec!(PerElem { name: "C".to_string(), coef: 1, pos: 0, len: 1 })),
                         vec!(vec!(PerElem { name: "H".to_string(), coef: 1, pos: 5, len: 1 })));
         check_raw_result!(raw_result, expected);
     }
 
+    #[test]
+    fn states() {
+        let mut parser = Parser::new("C(s) + O2(g) -> CO2");
+        let reaction = parser.parse_reaction().unwrap();
+        let states: Vec<Option<State>> = reaction.species().map(|s| s.state).collect();
+        let raw_result: CTResult<_> = Ok(sides(reaction));
+        let expected = (vec!(vec!(PerElem { name: "C".to_string(), coef: 1, pos: 0, len: 1 }),
+                             vec!(PerElem { name: "O".to_string(), coef: 2, pos: 7, len: 1 })),
+                        vec!(vec!(PerElem { name: "C".to_string(), coef: 1, pos: 16, len: 1 },
+                                  PerElem { name: "O".to_string(), coef: 2, pos: 17, len: 1 })));
+        check_raw_result!(raw_result, expected);
+        assert_eq!(states, vec!(Some(State::Solid), Some(State::Gas), None));
+    }
+
+    #[test]
+    fn state_after_parens() {
+        let mut parser = Parser::new("Ca(OH)2(aq)");
+        let side = parser.parse_side().unwrap();
+        assert_eq!(side[0].state, Some(State::Aqueous));
+        let raw_result: CTResult<_> = Ok(molecules(side));
+        let expected = vec!(vec!(PerElem { name: "Ca".to_string(), coef: 1, pos: 0, len: 2 },
+                                 PerElem { name: "O".to_string(), coef: 2, pos: 3, len: 1 },
+                                 PerElem { name: "H".to_string(), coef: 2, pos: 4, len: 1 }));
+        check_raw_result!(raw_result, expected);
+    }
+
     #[test]
     fn empty() {
         let mut parser = Parser::new("");
@@ -340,9 +797,132 @@ mod test {
         assert!(parser.parse_reaction().is_err());
     }
 
+    #[test]
+    fn unicode_coefs() {
+        let mut parser = Parser::new("H₂O");
+        let raw_result = parser.parse_molecule();
+        let expected = vec!(PerElem { name: "H".to_string(), coef: 2, pos: 0, len: 1 },
+                            PerElem { name: "O".to_string(), coef: 1, pos: 4, len: 1 });
+        check_raw_result!(raw_result, expected);
+    }
+
+    #[test]
+    fn unicode_charge() {
+        let mut parser = Parser::new("SO₄²⁻");
+        let raw_result = parser.parse_molecule();
+        let expected = vec!(PerElem { name: "S".to_string(), coef: 1, pos: 0, len: 1 },
+                            PerElem { name: "O".to_string(), coef: 4, pos: 1, len: 1 },
+                            PerElem { name: "-".to_string(), coef: 2, pos: 5, len: 5 });
+        check_raw_result!(raw_result, expected);
+    }
+
+    #[test]
+    fn ascii_charge() {
+        let mut parser = Parser::new("Na^+ + Cl^- -> NaCl");
+        let raw_result = parser.parse_reaction().map(sides);
+        let expected = (vec!(vec!(PerElem { name: "Na".to_string(), coef: 1, pos: 0, len: 2 },
+                                  PerElem { name: "+".to_string(), coef: 1, pos: 2, len: 2 }),
+                             vec!(PerElem { name: "Cl".to_string(), coef: 1, pos: 7, len: 2 },
+                                  PerElem { name: "-".to_string(), coef: 1, pos: 9, len: 2 })),
+                        vec!(vec!(PerElem { name: "Na".to_string(), coef: 1, pos: 15, len: 2 },
+                                  PerElem { name: "Cl".to_string(), coef: 1, pos: 17, len: 2 })));
+        check_raw_result!(raw_result, expected);
+    }
+
+    #[test]
+    fn missing_charge_sign() {
+        let mut parser = Parser::new("Fe^3");
+        assert!(parser.parse_molecule().is_err());
+    }
+
+    #[test]
+    fn unicode_arrow() {
+        let mut parser = Parser::new("C → H");
+        let raw_result = parser.parse_reaction().map(sides);
+        let expected = (vec!(vec!(PerElem { name: "C".to_string(), coef: 1, pos: 0, len: 1 })),
+                        vec!(vec!(PerElem { name: "H".to_string(), coef: 1, pos: 6, len: 1 })));
+        check_raw_result!(raw_result, expected);
+    }
+
+    #[test]
+    fn unicode_error_pos() {
+        let mut parser = Parser::new("H₂O$");
+        let result = parser.parse_molecule();
+        assert_eq!(result.err().unwrap().pos, Some((5, 1)));
+    }
+
+    #[test]
+    fn is_done_unicode() {
+        let mut parser = Parser::new("H₂O₂ ");
+        parser.parse_molecule().unwrap();
+        assert!(parser.is_done());
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(split_sides("A + B → C"), vec!("A + B ", " C"));
+        assert_eq!(split_species("Na^+ + Cl^- + Fe^3+"), vec!("Na^+ ", " Cl^- ", " Fe^3+"));
+    }
+
+    #[test]
+    fn split_equilibrium() {
+        assert_eq!(split_sides("A <-> B"), vec!("A ", " B"));
+        assert_eq!(split_sides("A ->[heat] B"), vec!("A ", " B"));
+        assert_eq!(arrow_text("A →[heat] B"), "->[heat]");
+        assert_eq!(arrow_text("A ⇌ B"), "<=>");
+    }
+
+    #[test]
+    fn species_coefs() {
+        let mut parser = Parser::new("2 H2 + O2 -> 2H2O(l)");
+        let reaction = parser.parse_reaction().unwrap();
+        assert_eq!(reaction.coefs(), vec!(2, 1, 2));
+        assert_eq!(reaction.reactants[0].pos, (0, 4));
+        assert_eq!(reaction.products[0].pos, (13, 7));
+    }
+
+    #[test]
+    fn equilibrium_arrows() {
+        for input in ["N2 + 3 H2 <=> 2 NH3", "N2 + 3 H2 <-> 2 NH3", "N2 + 3 H2 ⇌ 2 NH3"].iter() {
+            let reaction = Parser::new(input).parse_reaction().unwrap();
+            assert_eq!(reaction.arrow, Arrow::Equilibrium);
+            assert_eq!(reaction.products.len(), 1);
+        }
+    }
+
+    #[test]
+    fn conditions() {
+        let mut parser = Parser::new("CaCO3 ->[heat, 900 °C] CaO + CO2");
+        let reaction = parser.parse_reaction().unwrap();
+        assert_eq!(reaction.arrow, Arrow::Forward);
+        assert_eq!(reaction.conditions, vec!("heat".to_string(), "900 °C".to_string()));
+        assert_eq!(reaction.products.len(), 2);
+    }
+
+    #[test]
+    fn unclosed_conditions() {
+        let mut parser = Parser::new("CaCO3 ->[heat CaO + CO2");
+        assert_eq!(parser.parse_reaction().err().unwrap().pos, Some((8, 1)));
+    }
+
     #[test]
     fn dangling_plus() {
         let mut parser = Parser::new("C + -> H");
         assert!(parser.parse_reaction().is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn all_errors() {
+        let mut parser = Parser::new("H2O + 2 + Ca(OH2 -> H2 + x");
+        let err = parser.parse_reaction().err().unwrap();
+        let positions: Vec<(usize, usize)> = err.labels.iter().map(|l| l.pos).collect();
+        assert_eq!(positions, vec!((6, 1), (16, 1), (25, 1)));
+    }
+
+    #[test]
+    fn recover_at_arrow() {
+        let mut parser = Parser::new("C + -> H + (");
+        let err = parser.parse_reaction().err().unwrap();
+        assert_eq!(err.labels.len(), 2);
+    }
+}
@@ -0,0 +1,173 @@
+//! The Python extension module, built with the `python` feature.
+//!
+//! The module is named `chemtool`, and wraps the functions at the top of the crate. Formulas are
+//! returned as dictionaries from symbol to count, and every `CTError` is raised as a
+//! `chemtool.ChemtoolError`, carrying the same fields as the JSON written for errors.
+
+use std::path::Path;
+use pyo3::prelude::*;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::types::PyDict;
+use crate::database::ElemDatabase;
+use crate::error::{CTResult, CTError};
+
+create_exception!(chemtool, ChemtoolError, PyException,
+                  "An error from chemtool, with its kind, description, position, labels and help");
+
+/// Turns an error into a `ChemtoolError`, pointing at the error in the given input
+///
+/// The exception holds the attributes `kind`, `desc`, `pos`, `labels` and `help`, which are the
+/// same as in the JSON for the error, and `message`, which is the full message printed by the
+/// program.
+fn to_py_err(py: Python, error: CTError, input: &str) -> PyErr {
+    let err = ChemtoolError::new_err(error.desc.clone());
+    let labels: Vec<((usize, usize), &str)> = error.labels.iter()
+                                                   .map(|l| (l.pos, l.text.as_str()))
+                                                   .collect();
+    let value = err.value(py);
+    // setting attributes on a fresh exception can only fail when out of memory
+    let _ = value.setattr("kind", format!("{:?}", error.kind))
+                 .and_then(|_| value.setattr("desc", error.desc.as_str()))
+                 .and_then(|_| value.setattr("pos", error.pos))
+                 .and_then(|_| value.setattr("labels", labels))
+                 .and_then(|_| value.setattr("help", error.help.as_deref()))
+                 .and_then(|_| value.setattr("message", error.message(Some(input), false)));
+    err
+}
+
+/// Opens the element database at the given path, or the one built into the library if there is
+/// none
+fn open_database(db_path: Option<&str>) -> CTResult<ElemDatabase> {
+    match db_path {
+        Some(path) => ElemDatabase::open(Path::new(path)),
+        None => Ok(ElemDatabase::embedded()),
+    }
+}
+
+/// Parses a formula into a dictionary from each element to its count
+///
+/// A charge is kept under the symbol `+` or `-`, e.g. `{'-': 2, 'O': 4, 'S': 1}` for `SO4^2-`.
+#[pyfunction]
+fn parse_formula<'py>(py: Python<'py>, formula: &str) -> PyResult<Bound<'py, PyDict>> {
+    let molecule = crate::parse_molecule(formula).map_err(|e| to_py_err(py, e, formula))?;
+    let counts = PyDict::new(py);
+    for elem in molecule.iter() {
+        counts.set_item(elem.name.as_str(), elem.coef)?;
+    }
+    Ok(counts)
+}
+
+/// Finds the molar mass of a formula in g/mol, using the element database at the given path if
+/// there is one
+#[pyfunction]
+#[pyo3(signature = (formula, db_path = None))]
+fn molar_mass(py: Python, formula: &str, db_path: Option<&str>) -> PyResult<f64> {
+    open_database(db_path)
+        .and_then(|mut database| crate::molar_mass(&mut database, formula))
+        .map_err(|e| to_py_err(py, e, formula))
+}
+
+/// Balances a reaction, returning it as text along with the coefficient of each species
+///
+/// The coefficients are given with the reactants first, e.g.
+/// `('C3H8 + 5 O2 -> 3 CO2 + 4 H2O', [1, 5, 3, 4])`.
+#[pyfunction]
+fn balance(py: Python, reaction: &str) -> PyResult<(String, Vec<u32>)> {
+    let balanced = crate::balance(reaction).map_err(|e| to_py_err(py, e, reaction))?;
+    Ok((balanced.to_string(), balanced.coefs()))
+}
+
+/// Looks up an element by its symbol, using the element database at the given path if there is
+/// one
+///
+/// The data is a dictionary with the `symbol`, `name`, `molar_mass` and `atomic_number` of the
+/// element.
+#[pyfunction]
+#[pyo3(signature = (symbol, db_path = None))]
+fn element<'py>(py: Python<'py>, symbol: &str, db_path: Option<&str>)
+                -> PyResult<Bound<'py, PyDict>> {
+    let data = open_database(db_path)
+        .and_then(|mut database| crate::element(&mut database, symbol))
+        .map_err(|e| to_py_err(py, e, symbol))?;
+    let dict = PyDict::new(py);
+    dict.set_item("symbol", data.short_name)?;
+    dict.set_item("name", data.long_name)?;
+    dict.set_item("molar_mass", data.mass)?;
+    dict.set_item("atomic_number", data.atomic_num)?;
+    Ok(dict)
+}
+
+#[pymodule]
+fn chemtool(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("ChemtoolError", m.py().get_type::<ChemtoolError>())?;
+    m.add_function(wrap_pyfunction!(parse_formula, m)?)?;
+    m.add_function(wrap_pyfunction!(molar_mass, m)?)?;
+    m.add_function(wrap_pyfunction!(balance, m)?)?;
+    m.add_function(wrap_pyfunction!(element, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::types::PyModule;
+
+    /// Runs the Python code with the module imported as `chemtool`
+    fn run(code: &str) {
+        Python::initialize();
+        Python::attach(|py| {
+            let chemtool = PyModule::new(py, "chemtool").unwrap();
+            super::chemtool(&chemtool).unwrap();
+            let globals = PyDict::new(py);
+            globals.set_item("chemtool", chemtool).unwrap();
+            let code = std::ffi::CString::new(code).unwrap();
+            if let Err(e) = py.run(code.as_c_str(), Some(&globals), None) {
+                panic!("{}", e);
+            }
+        });
+    }
+
+    #[test]
+    fn parse() {
+        run("assert chemtool.parse_formula('SO4^2-') == {'-': 2, 'O': 4, 'S': 1}");
+    }
+
+    #[test]
+    fn balance_reaction() {
+        run("assert chemtool.balance('C3H8 + O2 -> CO2 + H2O') == \
+             ('C3H8 + 5 O2 -> 3 CO2 + 4 H2O', [1, 5, 3, 4])");
+    }
+
+    #[test]
+    fn mass_and_element() {
+        run("assert abs(chemtool.molar_mass('H2O') - 18.015) < 0.01\n\
+             assert chemtool.element('Fe')['atomic_number'] == 26");
+    }
+
+    #[test]
+    fn database_path() {
+        run("\
+try:
+    chemtool.molar_mass('H2O', db_path='no/such/file.csv')
+    assert False
+except chemtool.ChemtoolError as e:
+    assert e.kind == 'DatabaseError'
+");
+    }
+
+    #[test]
+    fn error() {
+        run("\
+try:
+    chemtool.parse_formula('H2O)')
+    assert False
+except chemtool.ChemtoolError as e:
+    assert str(e) == 'Missing opening parentheses'
+    assert e.kind == 'InputError'
+    assert e.pos == (3, 1)
+    assert e.help is None
+    assert e.message == 'Missing opening parentheses\\n    H2O)\\n       ^\\n'
+");
+    }
+}
@@ -0,0 +1,280 @@
+//! Functions for writing formulas and reactions as markup, for use in reports.
+//!
+//! The following styles are supported:
+//! * Plain ASCII, e.g. `H2O`, which is how formulas are normally written by chemtool.
+//! * LaTeX, e.g. `\mathrm{H_{2}O}`.
+//! * The `\ce` command from the mhchem LaTeX package, e.g. `\ce{H2O}`.
+//! * HTML, e.g. `H<sub>2</sub>O`.
+//! * Unicode subscript digits, e.g. `H₂O`.
+//!
+//! Charges are written as superscripts in every style, e.g. `SO₄²⁻` or `SO4^2-` in plain ASCII.
+//! The conditions of a reaction are free text, so they are escaped for the markup of the style.
+
+use crate::elem::{Molecule, Arrow, Reaction};
+use crate::balance;
+use crate::error::{CTResult, CTError};
+use crate::error::CTErrorKind::UsageError;
+
+static SUBSCRIPTS: [char; 10] = ['₀', '₁', '₂', '₃', '₄', '₅', '₆', '₇', '₈', '₉'];
+static SUPERSCRIPTS: [char; 10] = ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Style {
+    Plain,
+    Latex,
+    Mhchem,
+    Html,
+    Unicode,
+}
+
+impl Style {
+    /// Finds the style with the given name, as given to the --render option
+    pub fn from_name(name: &str) -> CTResult<Style> {
+        match name {
+            "plain" => Ok(Style::Plain),
+            "latex" => Ok(Style::Latex),
+            "mhchem" => Ok(Style::Mhchem),
+            "html" => Ok(Style::Html),
+            "unicode" => Ok(Style::Unicode),
+            _ => Err(CTError::new(UsageError, format!("Unknown render style: {:?}", name))),
+        }
+    }
+}
+
+/// Writes a molecule in the given style
+pub fn render_molecule(molecule: &Molecule, style: Style) -> String {
+    let formula = render_formula(molecule, style);
+    match style {
+        Style::Latex => format!("\\mathrm{{{}}}", formula),
+        Style::Mhchem => format!("\\ce{{{}}}", formula),
+        _ => formula,
+    }
+}
+
+/// Writes a balanced reaction in the given style
+///
+/// Every coefficient is written, including those of 1, like `balance::format_balanced` does it
+/// for the plain style.
+pub fn render_balanced(reaction: &Reaction, style: Style) -> String {
+    if style == Style::Plain {
+        return balance::format_balanced(reaction);
+    }
+    let species: Vec<String> = reaction.species().map(|s| {
+        // in mhchem the whole reaction is put in a single \ce, so each molecule is not wrapped
+        let formula = if style == Style::Mhchem {
+            render_formula(&s.molecule, style)
+        } else {
+            render_molecule(&s.molecule, style)
+        };
+        match style {
+            Style::Latex => format!("{}\\,{}", s.coef, formula),
+            _ => format!("{} {}", s.coef, formula),
+        }
+    }).collect();
+    let lhs_len = reaction.reactants.len();
+    let reaction = format!("{} {} {}", species[..lhs_len].join(" + "),
+                           render_arrow(reaction, style), species[lhs_len..].join(" + "));
+    if style == Style::Mhchem {
+        format!("\\ce{{{}}}", reaction)
+    } else {
+        reaction
+    }
+}
+
+/// Writes the arrow of a reaction in the given style, along with its conditions
+fn render_arrow(reaction: &Reaction, style: Style) -> String {
+    let conditions = escape(reaction.conditions.join(", ").as_str(), style);
+    match (style, reaction.arrow) {
+        // LaTeX has extensible arrows, which the conditions are written above
+        (Style::Latex, Arrow::Forward) if !conditions.is_empty() => {
+            format!("\\xrightarrow{{\\text{{{}}}}}", conditions)
+        },
+        (Style::Latex, Arrow::Equilibrium) if !conditions.is_empty() => {
+            format!("\\xrightleftharpoons{{\\text{{{}}}}}", conditions)
+        },
+        (Style::Latex, Arrow::Forward) => "\\rightarrow".to_string(),
+        (Style::Latex, Arrow::Equilibrium) => "\\rightleftharpoons".to_string(),
+        // mhchem writes its arrows and conditions like chemtool does
+        (Style::Plain, _) => reaction.arrow_text(),
+        (Style::Mhchem, arrow) if !conditions.is_empty() => format!("{}[{}]", arrow, conditions),
+        (Style::Mhchem, arrow) => format!("{}", arrow),
+        (_, arrow) => {
+            let arrow = match (style, arrow) {
+                (Style::Html, Arrow::Forward) => "&rarr;",
+                (Style::Html, Arrow::Equilibrium) => "&rlhar;",
+                (_, Arrow::Forward) => "→",
+                (_, Arrow::Equilibrium) => "⇌",
+            };
+            if conditions.is_empty() {
+                arrow.to_string()
+            } else {
+                format!("{}[{}]", arrow, conditions)
+            }
+        },
+    }
+}
+
+/// Escapes the characters in free text which have a meaning in the markup of the style
+///
+/// In mhchem, `_` and `^` are left alone, since the conditions are read as formulas there.
+fn escape(text: &str, style: Style) -> String {
+    let mut out = String::new();
+    for ch in text.chars() {
+        match (style, ch) {
+            (Style::Html, '&') => out.push_str("&amp;"),
+            (Style::Html, '<') => out.push_str("&lt;"),
+            (Style::Html, '>') => out.push_str("&gt;"),
+            (Style::Latex, '\\') => out.push_str("\\textbackslash{}"),
+            (Style::Latex, '^') => out.push_str("\\textasciicircum{}"),
+            (Style::Latex, '~') => out.push_str("\\textasciitilde{}"),
+            (Style::Latex, '_') | (Style::Latex, '{') | (Style::Latex, '}') |
+            (Style::Latex, '%') | (Style::Latex, '#') | (Style::Latex, '&') |
+            (Style::Latex, '$') | (Style::Mhchem, '%') | (Style::Mhchem, '#') |
+            (Style::Mhchem, '&') | (Style::Mhchem, '$') => {
+                out.push('\\');
+                out.push(ch);
+            },
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Writes the elements of a molecule, without anything around them
+fn render_formula(molecule: &Molecule, style: Style) -> String {
+    let mut out = String::new();
+    for elem in molecule.iter().filter(|e| !e.is_charge()) {
+        out.push_str(elem.name.as_str());
+        if elem.coef > 1 {
+            out.push_str(render_coef(elem.coef, style).as_str());
+        }
+    }
+    // like in plain text, the charge is always written last
+    for charge in molecule.iter().filter(|e| e.is_charge()) {
+        out.push_str(render_charge(charge.coef, charge.name.as_str(), style).as_str());
+    }
+    out
+}
+
+fn render_charge(size: u32, sign: &str, style: Style) -> String {
+    // a charge of 1 is written with just the sign
+    let size = if size > 1 { format!("{}", size) } else { String::new() };
+    match style {
+        Style::Plain | Style::Mhchem => format!("^{}{}", size, sign),
+        Style::Latex => format!("^{{{}{}}}", size, sign),
+        Style::Html => format!("<sup>{}{}</sup>", size, sign),
+        Style::Unicode => {
+            let mut out: String = size.chars()
+                                      .map(|ch| SUPERSCRIPTS[ch.to_digit(10).unwrap() as usize])
+                                      .collect();
+            out.push(if sign == "+" { '⁺' } else { '⁻' });
+            out
+        },
+    }
+}
+
+fn render_coef(coef: u32, style: Style) -> String {
+    match style {
+        Style::Plain | Style::Mhchem => format!("{}", coef),
+        Style::Latex => format!("_{{{}}}", coef),
+        Style::Html => format!("<sub>{}</sub>", coef),
+        Style::Unicode => {
+            format!("{}", coef).chars()
+                               .map(|ch| SUBSCRIPTS[ch.to_digit(10).unwrap() as usize])
+                               .collect()
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elem::PerElem;
+
+    macro_rules! dummy_elem(
+        ($name:expr, $coef:expr) => (
+            PerElem { name: $name.to_string(), coef: $coef, pos: 0, len: 1 }
+        );
+    );
+
+    fn water() -> Vec<PerElem> {
+        vec!(dummy_elem!("H", 2), dummy_elem!("O", 1))
+    }
+
+    fn combustion() -> Reaction {
+        let mut reaction = Reaction::new(vec!(vec!(dummy_elem!("H", 2)), vec!(dummy_elem!("O", 2))),
+                                         vec!(water()));
+        reaction.set_coefs(&[2, 1, 2]);
+        reaction
+    }
+
+    #[test]
+    fn molecule() {
+        assert_eq!(render_molecule(&water(), Style::Plain), "H2O");
+        assert_eq!(render_molecule(&water(), Style::Latex), "\\mathrm{H_{2}O}");
+        assert_eq!(render_molecule(&water(), Style::Mhchem), "\\ce{H2O}");
+        assert_eq!(render_molecule(&water(), Style::Html), "H<sub>2</sub>O");
+        assert_eq!(render_molecule(&water(), Style::Unicode), "H₂O");
+    }
+
+    #[test]
+    fn multi_digit() {
+        let molecule = vec!(dummy_elem!("C", 12), dummy_elem!("H", 22));
+        assert_eq!(render_molecule(&molecule, Style::Unicode), "C₁₂H₂₂");
+    }
+
+    #[test]
+    fn charge() {
+        let sulfate = vec!(dummy_elem!("S", 1), dummy_elem!("O", 4), dummy_elem!("-", 2));
+        assert_eq!(render_molecule(&sulfate, Style::Unicode), "SO₄²⁻");
+        assert_eq!(render_molecule(&sulfate, Style::Mhchem), "\\ce{SO4^2-}");
+        assert_eq!(render_molecule(&sulfate, Style::Latex), "\\mathrm{SO_{4}^{2-}}");
+        assert_eq!(render_molecule(&sulfate, Style::Html), "SO<sub>4</sub><sup>2-</sup>");
+        let sodium = vec!(dummy_elem!("Na", 1), dummy_elem!("+", 1));
+        assert_eq!(render_molecule(&sodium, Style::Unicode), "Na⁺");
+    }
+
+    #[test]
+    fn reaction() {
+        assert_eq!(render_balanced(&combustion(), Style::Mhchem), "\\ce{2 H2 + 1 O2 -> 2 H2O}");
+        assert_eq!(render_balanced(&combustion(), Style::Unicode), "2 H₂ + 1 O₂ → 2 H₂O");
+        assert_eq!(render_balanced(&combustion(), Style::Latex),
+                   "2\\,\\mathrm{H_{2}} + 1\\,\\mathrm{O_{2}} \\rightarrow 2\\,\\mathrm{H_{2}O}");
+    }
+
+    #[test]
+    fn plain_reaction() {
+        assert_eq!(render_balanced(&combustion(), Style::Plain), "2 H2 + 1 O2 -> 2 H2O");
+    }
+
+    #[test]
+    fn equilibrium_with_conditions() {
+        let mut reaction = combustion();
+        reaction.arrow = Arrow::Equilibrium;
+        reaction.conditions = vec!("Pt".to_string());
+        assert_eq!(render_balanced(&reaction, Style::Mhchem),
+                   "\\ce{2 H2 + 1 O2 <=>[Pt] 2 H2O}");
+        assert_eq!(render_balanced(&reaction, Style::Unicode), "2 H₂ + 1 O₂ ⇌[Pt] 2 H₂O");
+        assert_eq!(render_balanced(&reaction, Style::Latex),
+                   "2\\,\\mathrm{H_{2}} + 1\\,\\mathrm{O_{2}} \\xrightleftharpoons{\\text{Pt}} \
+                    2\\,\\mathrm{H_{2}O}");
+    }
+
+    #[test]
+    fn escaped_conditions() {
+        let mut reaction = combustion();
+        reaction.conditions = vec!("T<100 °C & 5% Pt_cat #2".to_string());
+        let arrow = |style| render_arrow(&reaction, style);
+        assert_eq!(arrow(Style::Html), "&rarr;[T&lt;100 °C &amp; 5% Pt_cat #2]");
+        assert_eq!(arrow(Style::Latex),
+                   "\\xrightarrow{\\text{T<100 °C \\& 5\\% Pt\\_cat \\#2}}");
+        assert_eq!(arrow(Style::Mhchem), "->[T<100 °C \\& 5\\% Pt_cat \\#2]");
+        assert_eq!(arrow(Style::Unicode), "→[T<100 °C & 5% Pt_cat #2]");
+        assert_eq!(arrow(Style::Plain), "->[T<100 °C & 5% Pt_cat #2]");
+    }
+
+    #[test]
+    fn unknown_style() {
+        assert!(Style::from_name("rtf").is_err());
+    }
+}
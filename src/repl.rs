@@ -0,0 +1,346 @@
+//! An interactive prompt for working on formulas and reactions.
+//!
+//! Each line typed at the prompt is one of the following:
+//! * A formula, whose molar mass is printed, or a reaction, which is balanced. These may also be
+//!   preceded by the name of the command, e.g. `mass H2O` or `balance H2 + O2 -> H2O`.
+//! * An expression using molar masses, e.g. `2 * M(H2O)`, which is evaluated. This may also be
+//!   preceded by `calc`.
+//! * An assignment of a formula or reaction to a variable, e.g. `x = C6H12O6`. The variable can
+//!   then be used in place of a formula or reaction, e.g. `x + O2 -> CO2 + H2O`.
+//! * One of the commands `history`, `vars`, `help` or `quit`.
+//! * `!n`, which runs line number `n` from the history again, or `!!` for the previous line.
+//!
+//! The variable `_` always holds the formula or reaction which was last worked on.
+
+use std::io;
+use std::io::Write;
+use chemtool::{parser, mass, elem, calc, balance, suggest};
+use chemtool::parser::Parser;
+use chemtool::database::ElemDatabase;
+use chemtool::compounds::CompoundDatabase;
+use chemtool::error::{CTResult, CTError};
+use chemtool::error::CTErrorKind::{InputError, UsageError};
+
+const PROMPT: &str = "> ";
+
+const HELP: &str = "\
+Type a formula to get its molar mass, or a reaction to balance it, e.g.
+    > C6H12O6
+    > C3H8 + O2 -> CO2 + H2O
+The following may also be typed:
+    mass <formula>          Print the molar mass of the formula.
+    balance <reaction>      Balance the reaction.
+    calc <expression>       Evaluate an expression, e.g. 2 * M(H2O) + 1 g.
+    <name> = <formula>      Store a formula or reaction in a variable.
+    history                 List the previous lines.
+    !<n>                    Run line number n from the history again.
+    !!                      Run the previous line again.
+    vars                    List the variables.
+    help                    Display this message.
+    quit                    Leave the prompt.
+The variable _ holds the formula or reaction which was last worked on.";
+
+pub struct Repl {
+    database: ElemDatabase,
+    compounds: Option<CompoundDatabase>,
+    vars: Vec<(String, String)>,
+    history: Vec<String>,
+}
+
+impl Repl {
+    /// Makes a new prompt, which uses the given databases for every line
+    pub fn new(database: ElemDatabase, compounds: Option<CompoundDatabase>) -> Repl {
+        Repl { database, compounds, vars: Vec::new(), history: Vec::new() }
+    }
+
+    /// Reads and runs lines from stdin until the user quits, or the input ends
+    pub fn run(&mut self) {
+        let stdin = io::stdin();
+        loop {
+            print!("{}", PROMPT);
+            let _ = io::stdout().flush();
+            let mut line = String::new();
+            match stdin.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => (),
+            }
+            if !self.run_line(line.trim()) {
+                break;
+            }
+        }
+    }
+
+    /// Runs a single line typed at the prompt, and prints the result or error
+    ///
+    /// Returns false if the user wants to quit.
+    pub fn run_line(&mut self, line: &str) -> bool {
+        if line.is_empty() {
+            return true;
+        }
+        let line = match self.expand_history(line) {
+            Ok(expanded) => {
+                if expanded != line {
+                    println!("{}", expanded);
+                }
+                expanded
+            },
+            Err(e) => {
+                e.print(None);
+                return true;
+            },
+        };
+        self.history.push(line.clone());
+
+        match line.as_str() {
+            "quit" | "exit" => return false,
+            "help" => println!("{}", HELP),
+            "history" => {
+                for (i, entry) in self.history.iter().enumerate() {
+                    println!("{: >4}  {}", i + 1, entry);
+                }
+            },
+            "vars" => {
+                for (name, value) in self.vars.iter() {
+                    println!("{} = {}", name, value);
+                }
+            },
+            _ => {
+                // errors are shown against the line which was actually worked on, which is the
+                // typed line unless some variables or names were replaced
+                let (input, result) = self.eval(line.as_str());
+                match result {
+                    Err(ref e) if e.kind == InputError => e.print(Some(input.as_str())),
+                    Err(ref e) => e.print(None),
+                    Ok(_) => (),
+                }
+            },
+        }
+        true
+    }
+
+    /// Evaluates an assignment, formula or reaction
+    ///
+    /// Returns the line in which the positions of any error can be found, along with the result.
+    fn eval(&mut self, line: &str) -> (String, CTResult<()>) {
+        if let Some((name, value)) = split_assignment(line) {
+            let value = self.resolve(value);
+            let result = self.check_species(value.as_str());
+            if result.is_ok() {
+                println!("{} = {}", name, value);
+                self.set_var(name, value.clone());
+            }
+            return (value, result);
+        }
+
+        // an explicit command is stripped, while remembering where the rest of the line starts
+        let word = line.split(' ').next().unwrap_or("");
+        let (cmd, rest) = if word == "mass" || word == "balance" || word == "calc" {
+            (word, line[word.len()..].trim_start())
+        } else if line.contains("M(") {
+            ("calc", line)
+        } else if parser::is_reaction(line) {
+            ("balance", line)
+        } else {
+            ("mass", line)
+        };
+        let offset = line.len() - rest.len();
+
+        if cmd == "calc" {
+            // variables are only replaced inside M(...), which is done while evaluating
+            let result = self.calc(rest);
+            return (line.to_string(), result.map_err(|e| e.shift(offset)));
+        }
+        let input = self.resolve(rest);
+        if input.as_str() != rest {
+            // show the user what is actually being worked on
+            println!("= {}", input);
+        }
+        let result = if cmd == "balance" {
+            self.balance(input.as_str())
+        } else {
+            self.mass(input.as_str())
+        };
+        if input.as_str() == rest {
+            // nothing was replaced, so the error can be shown against the typed line
+            (line.to_string(), result.map_err(|e| e.shift(offset)))
+        } else {
+            (input, result)
+        }
+    }
+
+    fn mass(&mut self, input: &str) -> CTResult<()> {
+        let molecule = match parser::parse_single_molecule(input) {
+            Ok(molecule) => elem::strip_charge(molecule),
+            Err(e) => return Err(suggest::add_help(e, input, &mut self.database)),
+        };
+        let data = match self.database.get_data(&molecule) {
+            Ok(data) => data,
+            Err(e) => return Err(suggest::add_help(e, input, &mut self.database)),
+        };
+        mass::pretty_print_data(&data, &molecule);
+        self.set_var("_", input.to_string());
+        Ok(())
+    }
+
+    fn balance(&mut self, input: &str) -> CTResult<()> {
+        let mut parser = Parser::new(input);
+        let mut reaction = match parser.parse_reaction() {
+            Ok(reaction) => reaction,
+            Err(e) => return Err(suggest::add_help(e, input, &mut self.database)),
+        };
+        reaction.balance()?;
+        balance::pretty_print_balanced(&reaction);
+        self.set_var("_", input.to_string());
+        Ok(())
+    }
+
+    fn calc(&mut self, input: &str) -> CTResult<()> {
+        let Repl { ref mut database, ref compounds, ref vars, .. } = *self;
+        let result = calc::evaluate(input, |formula| {
+            let formula = substitute(formula, vars);
+            calc::molar_mass(&mut *database, compounds.as_ref(), formula.as_str())
+        })?;
+        println!("{}", result);
+        Ok(())
+    }
+
+    /// Checks that the value of an assignment is either a formula or a reaction
+    fn check_species(&self, value: &str) -> CTResult<()> {
+        let mut parser = Parser::new(value);
+        if parser::is_reaction(value) {
+            parser.parse_reaction()?;
+        } else {
+            parser.parse_molecule()?;
+            parser.parse_state();
+        }
+        if parser.is_done() {
+            Ok(())
+        } else {
+            Err(CTError::new(InputError, "A variable must hold a single formula or reaction"))
+        }
+    }
+
+    /// Replaces the variables and compound names in the input by what they stand for
+    fn resolve(&mut self, input: &str) -> String {
+        let Repl { ref mut database, ref compounds, ref vars, .. } = *self;
+        let substituted = substitute(input, vars);
+        match *compounds {
+            Some(ref db) if parser::is_reaction(substituted.as_str()) => {
+                db.resolve_reaction(substituted.as_str(), database).0
+            },
+            Some(ref db) => db.resolve_molecule(substituted.as_str(), database).0,
+            None => substituted,
+        }
+    }
+
+    /// Replaces a line starting with `!` by the line it refers to in the history
+    fn expand_history(&self, line: &str) -> CTResult<String> {
+        if !line.starts_with("!") {
+            return Ok(line.to_string());
+        }
+        let index = if line == "!!" {
+            Some(self.history.len())
+        } else {
+            line[1..].parse::<usize>().ok()
+        };
+        match index {
+            Some(i) if i >= 1 && i <= self.history.len() => Ok(self.history[i - 1].clone()),
+            _ => Err(CTError::new(UsageError, format!("No such line in the history: {:?}", line))),
+        }
+    }
+
+    fn set_var(&mut self, name: &str, value: String) {
+        if let Some(var) = self.vars.iter_mut().find(|&&mut (ref n, _)| n.as_str() == name) {
+            var.1 = value;
+            return;
+        }
+        self.vars.push((name.to_string(), value));
+    }
+}
+
+/// Splits a line of the form `<name> = <value>` into the name and value
+///
+/// Names must start with a lowercase letter or `_`, so they can never be mistaken for a formula.
+fn split_assignment(line: &str) -> Option<(&str, &str)> {
+    let mut parts = line.splitn(2, '=');
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(value)) if is_var_name(name.trim()) => Some((name.trim(), value.trim())),
+        _ => None,
+    }
+}
+
+fn is_var_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|ch| ch.is_lowercase() || ch == '_') &&
+        name.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
+/// Replaces every species in a formula or reaction which is the name of a variable by its value
+///
+/// If no variables were used, the input is returned untouched.
+fn substitute(input: &str, vars: &[(String, String)]) -> String {
+    let lookup = |token: &str| {
+        vars.iter().find(|&(name, _)| name.as_str() == token.trim()).map(|v| v.1.clone())
+    };
+    let mut replaced = false;
+    let mut sides = Vec::new();
+    for side in parser::split_sides(input).into_iter() {
+        let mut species = Vec::new();
+        for token in parser::split_species(side).into_iter() {
+            match lookup(token) {
+                Some(value) => {
+                    species.push(value);
+                    replaced = true;
+                },
+                None => species.push(token.trim().to_string()),
+            }
+        }
+        sides.push(species.join(" + "));
+    }
+
+    if replaced {
+        sides.join(format!(" {} ", parser::arrow_text(input)).as_str())
+    } else {
+        input.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{split_assignment, substitute};
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|&(a, b)| (a.to_string(), b.to_string())).collect()
+    }
+
+    #[test]
+    fn assignment() {
+        assert_eq!(split_assignment("x = C6H12O6"), Some(("x", "C6H12O6")));
+        assert_eq!(split_assignment("glucose_2=C6H12O6"), Some(("glucose_2", "C6H12O6")));
+    }
+
+    #[test]
+    fn not_assignment() {
+        assert_eq!(split_assignment("C6H12O6"), None);
+        assert_eq!(split_assignment("H2O = x"), None);
+        assert_eq!(split_assignment("my var = H2O"), None);
+    }
+
+    #[test]
+    fn substitute_molecule() {
+        let vars = vars(&[("x", "C6H12O6")]);
+        assert_eq!(substitute("x", &vars), "C6H12O6");
+        assert_eq!(substitute("H2O", &vars), "H2O");
+    }
+
+    #[test]
+    fn substitute_reaction() {
+        let vars = vars(&[("x", "C6H12O6"), ("_", "O2")]);
+        assert_eq!(substitute("x + _ -> CO2 + H2O", &vars), "C6H12O6 + O2 -> CO2 + H2O");
+    }
+
+    #[test]
+    fn substitute_untouched() {
+        let input = "C3H8 +  O2 -> CO2 + H2O";
+        assert_eq!(substitute(input, &vars(&[("x", "H2O")])), input);
+    }
+}
@@ -0,0 +1,419 @@
+//! Functions for the common bench calculations on solutions.
+//!
+//! Given the molar mass of the solute, and a few of the quantities describing a solution, the
+//! remaining quantities are derived from the following relations:
+//! * mass = molarity * volume * M
+//! * mass = molality * solvent mass * M
+//! * molarity = mass percent * density / M
+//! * molality = mass percent / ((1 - mass percent) * M)
+//!
+//! Dilutions are handled seperately, using C1V1 = C2V2.
+
+use crate::units;
+use crate::json::Json;
+use crate::output;
+use crate::error::{CTResult, CTError};
+use crate::error::CTErrorKind::{InputError, UsageError};
+
+/// The quantities describing a solution. Quantities which are not known are None.
+///
+/// All quantities are stored in the base units from the units module, i.e. mol/L, L, g, mol/kg,
+/// g, a fraction between 0 and 1, and g/mL respectively.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SolutionData {
+    pub molarity: Option<f64>,
+    pub volume: Option<f64>,
+    pub mass: Option<f64>,
+    pub molality: Option<f64>,
+    pub solvent: Option<f64>,
+    pub mass_percent: Option<f64>,
+    pub density: Option<f64>,
+}
+
+/// The four quantities in the dilution equation C1V1 = C2V2
+#[derive(Debug, PartialEq, Clone)]
+pub struct Dilution {
+    pub c1: Option<f64>,
+    pub v1: Option<f64>,
+    pub c2: Option<f64>,
+    pub v2: Option<f64>,
+}
+
+impl SolutionData {
+    /// Creates a SolutionData from `key=value` pairs given by the user
+    ///
+    /// A value of `?` marks the quantity as unknown, which is the same as not giving it at all.
+    pub fn from_assignments(assigns: &[(String, String)]) -> CTResult<SolutionData> {
+        let mut data = SolutionData {
+            molarity: None,
+            volume: None,
+            mass: None,
+            molality: None,
+            solvent: None,
+            mass_percent: None,
+            density: None,
+        };
+        for (key, value) in assigns.iter() {
+            let (field, unit_table) = match key.as_str() {
+                "c" | "molarity" => (&mut data.molarity, units::MOLARITY),
+                "V" | "volume" => (&mut data.volume, units::VOLUME),
+                "m" | "mass" => (&mut data.mass, units::MASS),
+                "b" | "molality" => (&mut data.molality, units::MOLALITY),
+                "solvent" => (&mut data.solvent, units::MASS),
+                "w" | "percent" => (&mut data.mass_percent, units::FRACTION),
+                "rho" | "density" => (&mut data.density, units::DENSITY),
+                _ => {
+                    let desc = format!("Unknown solution quantity: {:?}", key);
+                    return Err(CTError::new(UsageError, desc));
+                },
+            };
+            if value.as_str() != "?" {
+                *field = Some(units::parse_quantity(value.as_str(), unit_table)?);
+            }
+        }
+        Ok(data)
+    }
+}
+
+impl Dilution {
+    /// Creates a Dilution from `key=value` pairs given by the user
+    ///
+    /// A value of `?` marks the quantity as unknown, which is the same as not giving it at all.
+    pub fn from_assignments(assigns: &[(String, String)]) -> CTResult<Dilution> {
+        let mut dil = Dilution { c1: None, v1: None, c2: None, v2: None };
+        for (key, value) in assigns.iter() {
+            let (field, unit_table) = match key.as_str() {
+                "c1" => (&mut dil.c1, units::MOLARITY),
+                "V1" => (&mut dil.v1, units::VOLUME),
+                "c2" => (&mut dil.c2, units::MOLARITY),
+                "V2" => (&mut dil.v2, units::VOLUME),
+                _ => {
+                    let desc = format!("Unknown dilution quantity: {:?}", key);
+                    return Err(CTError::new(UsageError, desc));
+                },
+            };
+            if value.as_str() != "?" {
+                *field = Some(units::parse_quantity(value.as_str(), unit_table)?);
+            }
+        }
+        Ok(dil)
+    }
+}
+
+/// Derives every quantity which can be found from the given ones
+///
+/// The function errors if the given quantities are not enough to derive anything new, if any of
+/// them are not positive, if the mass percent is 100 % or more, or if the given quantities do not
+/// agree with each other.
+pub fn solve(mut data: SolutionData, molar_mass: f64) -> CTResult<SolutionData> {
+    units::check_positive("molarity", data.molarity)?;
+    units::check_positive("volume", data.volume)?;
+    units::check_positive("mass", data.mass)?;
+    units::check_positive("molality", data.molality)?;
+    units::check_positive("solvent mass", data.solvent)?;
+    units::check_positive("mass percent", data.mass_percent)?;
+    units::check_positive("density", data.density)?;
+    if let Some(w) = data.mass_percent.filter(|&w| w >= 1.0) {
+        let desc = format!("The mass percent must be below 100 %, found: {} %", w * 100.0);
+        return Err(CTError::new(InputError, desc));
+    }
+    let given = data.clone();
+    // each relation can provide the inputs for another one, so we keep going until nothing new is
+    // found. Quantities are only ever added, so this ends after at most one pass per quantity.
+    loop {
+        let before = known(&data);
+        let d = data.clone();
+        match (d.molarity, d.volume, d.mass) {
+            (Some(c), Some(v), None) => data.mass = Some(c * v * molar_mass),
+            (Some(c), None, Some(m)) => data.volume = Some(m / molar_mass / c),
+            (None, Some(v), Some(m)) => data.molarity = Some(m / molar_mass / v),
+            _ => (),
+        }
+        match (d.molality, d.solvent, d.mass) {
+            (Some(b), Some(s), None) => data.mass = Some(b * s / 1000.0 * molar_mass),
+            (Some(b), None, Some(m)) => data.solvent = Some(m / molar_mass / b * 1000.0),
+            (None, Some(s), Some(m)) => data.molality = Some(m / molar_mass / s * 1000.0),
+            _ => (),
+        }
+        if let (None, Some(m), Some(s)) = (d.mass_percent, d.mass, d.solvent) {
+            data.mass_percent = Some(m / (m + s));
+        }
+        if let Some(w) = d.mass_percent {
+            if d.molality.is_none() {
+                data.molality = Some(w / ((1.0 - w) * molar_mass) * 1000.0);
+            }
+            if let (None, Some(rho)) = (d.molarity, d.density) {
+                // the density is in g/mL, so we multiply by 1000 to get g/L
+                data.molarity = Some(w * rho * 1000.0 / molar_mass);
+            }
+        }
+        if known(&data) == before {
+            break;
+        }
+    }
+
+    if let Some(relation) = broken_relation(&data, molar_mass) {
+        let desc = format!("The given quantities are inconsistent: {} does not hold", relation);
+        Err(CTError::new(InputError, desc).with_help("leave out one of the quantities"))
+    } else if data == given {
+        Err(CTError::new(InputError, "Not enough quantities given to calculate anything"))
+    } else {
+        Ok(data)
+    }
+}
+
+/// Counts the known quantities of a solution
+fn known(data: &SolutionData) -> usize {
+    [data.molarity, data.volume, data.mass, data.molality, data.solvent, data.mass_percent,
+     data.density].iter().filter(|q| q.is_some()).count()
+}
+
+/// Finds a relation between the quantities which does not hold, if there is one
+///
+/// The derived quantities always agree with the relation they came from, so only a solution with
+/// more quantities given than needed can break one.
+fn broken_relation(d: &SolutionData, molar_mass: f64) -> Option<&'static str> {
+    let relations = [
+        ("mass = molarity * volume * M", d.mass,
+         d.molarity.zip(d.volume).map(|(c, v)| c * v * molar_mass)),
+        ("mass = molality * solvent mass * M", d.mass,
+         d.molality.zip(d.solvent).map(|(b, s)| b * s / 1000.0 * molar_mass)),
+        ("mass percent = mass / (mass + solvent mass)", d.mass_percent,
+         d.mass.zip(d.solvent).map(|(m, s)| m / (m + s))),
+        ("molality = mass percent / ((1 - mass percent) * M)", d.molality,
+         d.mass_percent.map(|w| w / ((1.0 - w) * molar_mass) * 1000.0)),
+        ("molarity = mass percent * density / M", d.molarity,
+         d.mass_percent.zip(d.density).map(|(w, rho)| w * rho * 1000.0 / molar_mass)),
+    ];
+    relations.iter().find(|&&(_, given, derived)| match (given, derived) {
+        (Some(given), Some(derived)) => !units::roughly_equal(given, derived),
+        _ => false,
+    }).map(|&(relation, _, _)| relation)
+}
+
+/// Finds the missing quantity in C1V1 = C2V2
+///
+/// Exactly one of the four quantities must be missing, and the others must be positive,
+/// otherwise the function errors.
+pub fn solve_dilution(dil: Dilution) -> CTResult<Dilution> {
+    units::check_positive("c1", dil.c1)?;
+    units::check_positive("V1", dil.v1)?;
+    units::check_positive("c2", dil.c2)?;
+    units::check_positive("V2", dil.v2)?;
+    match (dil.c1, dil.v1, dil.c2, dil.v2) {
+        (None, Some(v1), Some(c2), Some(v2)) => Ok(Dilution { c1: Some(c2 * v2 / v1), ..dil }),
+        (Some(c1), None, Some(c2), Some(v2)) => Ok(Dilution { v1: Some(c2 * v2 / c1), ..dil }),
+        (Some(c1), Some(v1), None, Some(v2)) => Ok(Dilution { c2: Some(c1 * v1 / v2), ..dil }),
+        (Some(c1), Some(v1), Some(c2), None) => Ok(Dilution { v2: Some(c1 * v1 / c2), ..dil }),
+        _ => Err(CTError::new(InputError, "Exactly one of c1, V1, c2 and V2 must be unknown")),
+    }
+}
+
+/// Pretty prints the known quantities of a solution
+pub fn pretty_print(data: &SolutionData, molar_mass: f64) {
+    println!("M:          {:.5} g/mol", molar_mass);
+    print_quantity("mass:", data.mass, "g");
+    print_quantity("volume:", data.volume, "L");
+    print_quantity("molarity:", data.molarity, "mol/L");
+    print_quantity("solvent:", data.solvent, "g");
+    print_quantity("molality:", data.molality, "mol/kg");
+    print_quantity("percent:", data.mass_percent.map(|w| w * 100.0), "%");
+    print_quantity("density:", data.density, "g/mL");
+}
+
+/// Pretty prints the quantities of a dilution
+pub fn pretty_print_dilution(dil: &Dilution) {
+    print_quantity("c1:", dil.c1, "mol/L");
+    print_quantity("V1:", dil.v1, "L");
+    print_quantity("c2:", dil.c2, "mol/L");
+    print_quantity("V2:", dil.v2, "L");
+}
+
+/// Makes the rows of a table with the known quantities of a solution
+pub fn to_rows(data: &SolutionData, molar_mass: f64) -> Vec<Vec<String>> {
+    output::quantity_rows(&[
+        ("molar_mass", Some(molar_mass), "g/mol"),
+        ("mass", data.mass, "g"),
+        ("volume", data.volume, "L"),
+        ("molarity", data.molarity, "mol/L"),
+        ("solvent", data.solvent, "g"),
+        ("molality", data.molality, "mol/kg"),
+        ("mass_percent", data.mass_percent.map(|w| w * 100.0), "%"),
+        ("density", data.density, "g/mL"),
+    ])
+}
+
+/// Makes the rows of a table with the quantities of a dilution
+pub fn dilution_to_rows(dil: &Dilution) -> Vec<Vec<String>> {
+    output::quantity_rows(&[
+        ("c1", dil.c1, "mol/L"),
+        ("V1", dil.v1, "L"),
+        ("c2", dil.c2, "mol/L"),
+        ("V2", dil.v2, "L"),
+    ])
+}
+
+/// Makes a JSON object with the quantities of a solution, where unknown quantities are null
+pub fn to_json(data: &SolutionData, molar_mass: f64) -> Json {
+    Json::object(vec!(
+        ("molar_mass", Json::Number(molar_mass)),
+        ("mass", Json::number(data.mass)),
+        ("volume", Json::number(data.volume)),
+        ("molarity", Json::number(data.molarity)),
+        ("solvent", Json::number(data.solvent)),
+        ("molality", Json::number(data.molality)),
+        ("mass_percent", Json::number(data.mass_percent.map(|w| w * 100.0))),
+        ("density", Json::number(data.density)),
+    ))
+}
+
+/// Makes a JSON object with the quantities of a dilution
+pub fn dilution_to_json(dil: &Dilution) -> Json {
+    Json::object(vec!(
+        ("c1", Json::number(dil.c1)),
+        ("V1", Json::number(dil.v1)),
+        ("c2", Json::number(dil.c2)),
+        ("V2", Json::number(dil.v2)),
+    ))
+}
+
+fn print_quantity(name: &str, value: Option<f64>, unit: &str) {
+    if let Some(value) = value {
+        println!("{: <11} {:.5} {}", name, value, unit);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    
+    fn empty() -> SolutionData {
+        SolutionData {
+            molarity: None,
+            volume: None,
+            mass: None,
+            molality: None,
+            solvent: None,
+            mass_percent: None,
+            density: None,
+        }
+    }
+
+    fn assert_close(result: Option<f64>, expected: f64) {
+        let result = result.unwrap();
+        assert!((result - expected).abs() < 1e-6, "{} != {}", result, expected);
+    }
+
+    #[test]
+    fn prepare() {
+        // 250 mL of 0.1 M CuSO4·5H2O
+        let data = SolutionData { molarity: Some(0.1), volume: Some(0.25), ..empty() };
+        let result = solve(data, 249.685).unwrap();
+        assert_close(result.mass, 6.242125);
+    }
+
+    #[test]
+    fn molarity_from_mass() {
+        let data = SolutionData { mass: Some(5.844), volume: Some(0.5), ..empty() };
+        let result = solve(data, 58.44).unwrap();
+        assert_close(result.molarity, 0.2);
+    }
+
+    #[test]
+    fn molality() {
+        let data = SolutionData { mass: Some(5.844), solvent: Some(200.0), ..empty() };
+        let result = solve(data, 58.44).unwrap();
+        assert_close(result.molality, 0.5);
+    }
+
+    #[test]
+    fn mass_percent() {
+        // concentrated sulfuric acid: 98 % w/w, 1.84 g/mL
+        let data = SolutionData { mass_percent: Some(0.98), density: Some(1.84), ..empty() };
+        let result = solve(data, 98.079).unwrap();
+        assert_close(result.molarity, 0.98 * 1840.0 / 98.079);
+        assert_close(result.molality, 0.98 / (0.02 * 98.079) * 1000.0);
+    }
+
+    #[test]
+    fn not_enough() {
+        let data = SolutionData { volume: Some(1.0), ..empty() };
+        assert!(solve(data, 1.0).is_err());
+    }
+
+    #[test]
+    fn not_positive() {
+        let data = SolutionData { mass: Some(0.0), volume: Some(0.5), ..empty() };
+        assert_eq!(solve(data, 58.44).unwrap_err().desc, "The mass must be positive, found: 0");
+        let data = SolutionData { molarity: Some(-0.1), volume: Some(0.5), ..empty() };
+        assert!(solve(data, 58.44).is_err());
+    }
+
+    #[test]
+    fn mass_percent_too_large() {
+        let data = SolutionData { mass_percent: Some(1.0), density: Some(1.0), ..empty() };
+        assert_eq!(solve(data, 98.079).unwrap_err().desc,
+                   "The mass percent must be below 100 %, found: 100 %");
+    }
+
+    #[test]
+    fn not_finite() {
+        let data = SolutionData { molarity: Some(f64::NAN), volume: Some(1.0), ..empty() };
+        assert!(solve(data, 58.44).is_err());
+        let data = SolutionData { molarity: Some(f64::INFINITY), volume: Some(1.0), ..empty() };
+        assert!(solve(data, 58.44).is_err());
+        let assigns = vec!(("c".to_string(), "NaN".to_string()));
+        assert!(SolutionData::from_assignments(&assigns).is_err());
+    }
+
+    #[test]
+    fn inconsistent() {
+        let data = SolutionData { molarity: Some(0.1), volume: Some(1.0), mass: Some(10.0),
+                                  ..empty() };
+        assert_eq!(solve(data, 58.44).unwrap_err().desc,
+                   "The given quantities are inconsistent: mass = molarity * volume * M does not \
+                    hold");
+        // rounded quantities are still accepted
+        let data = SolutionData { molarity: Some(0.1), volume: Some(1.0), mass: Some(5.84),
+                                  solvent: Some(1000.0), ..empty() };
+        assert_close(solve(data, 58.44).unwrap().molality, 0.1 * 5.84 / 5.844);
+    }
+
+    #[test]
+    fn dilution() {
+        let dil = Dilution { c1: Some(12.0), v1: None, c2: Some(1.0), v2: Some(0.5) };
+        let result = solve_dilution(dil).unwrap();
+        assert_close(result.v1, 0.5 / 12.0);
+    }
+
+    #[test]
+    fn dilution_too_many_unknowns() {
+        let dil = Dilution { c1: Some(12.0), v1: None, c2: None, v2: Some(0.5) };
+        assert!(solve_dilution(dil).is_err());
+    }
+
+    #[test]
+    fn dilution_not_positive() {
+        let dil = Dilution { c1: Some(12.0), v1: None, c2: Some(0.0), v2: Some(0.5) };
+        assert_eq!(solve_dilution(dil).unwrap_err().desc, "The c2 must be positive, found: 0");
+        let dil = Dilution { c1: Some(12.0), v1: None, c2: Some(f64::NAN), v2: Some(0.5) };
+        assert!(solve_dilution(dil).is_err());
+    }
+
+    #[test]
+    fn from_assignments() {
+        let assigns = vec!(("c".to_string(), "100mM".to_string()),
+                           ("V".to_string(), "250mL".to_string()),
+                           ("m".to_string(), "?".to_string()));
+        let result = SolutionData::from_assignments(&assigns).unwrap();
+        assert_close(result.molarity, 0.1);
+        assert_close(result.volume, 0.25);
+        assert_eq!(result.mass, None);
+    }
+
+    #[test]
+    fn unknown_quantity() {
+        let assigns = vec!(("x".to_string(), "1".to_string()));
+        assert!(SolutionData::from_assignments(&assigns).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Suggestions for element symbols which were probably mistyped.
+//!
+//! The following mistakes are caught:
+//! * Symbols written in the wrong case, e.g. `CL` for `Cl`, or `co` for either `Co` or `CO`.
+//! * Typos, found by the edit distance to the symbols and names of the elements, e.g. `Xa` for
+//!   `Xe`, or `Sodum` for `Na`.
+//! * Formulas which are valid, but are often typed by mistake, e.g. `CO` (carbon monoxide) when
+//!   cobalt (`Co`) was meant.
+
+use std::cmp::min;
+use crate::database::{ElemDatabase, ElemData};
+use crate::elem::PerElem;
+use crate::error::CTError;
+use crate::error::CTErrorKind::InputError;
+
+/// The most symbols suggested for a single mistake
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Adds a help note to an error in the input, with the symbols the user might have meant
+///
+/// Each position the error points at is checked for a mistyped symbol. The error is returned
+/// untouched if it already has a help note, or nothing better could be found.
+pub fn add_help(err: CTError, input: &str, database: &mut ElemDatabase) -> CTError {
+    if err.kind != InputError || err.help.is_some() {
+        return err;
+    }
+    let elems = match database.all_data() {
+        Ok(elems) => elems,
+        Err(_) => return err,
+    };
+    let positions: Vec<usize> = if err.labels.is_empty() {
+        err.pos.iter().map(|&(pos, _)| pos).collect()
+    } else {
+        err.labels.iter().map(|label| label.pos.0).collect()
+    };
+
+    let mut notes = Vec::new();
+    for pos in positions.into_iter() {
+        if let Some(note) = suggest_at(input, pos, &elems) {
+            notes.push(note);
+        }
+    }
+    let help = match notes.len() {
+        0 => return err,
+        1 => format!("did you mean {}?", alternatives(&notes[0].1)),
+        _ => {
+            let parts: Vec<String> = notes.iter().map(|(word, suggestions)| {
+                format!("for `{}` did you mean {}?", word, alternatives(suggestions))
+            }).collect();
+            parts.join(" ")
+        },
+    };
+    err.with_help(help)
+}
+
+/// Warns about a formula which is valid, but is often typed when a single element was meant
+///
+/// This is the case for two single letter symbols which together are also the symbol of an
+/// element written in the wrong case, e.g. `CO` and `Co`, or `NO` and `No`.
+pub fn ambiguity(input: &str, database: &mut ElemDatabase) -> Option<String> {
+    let formula = input.trim();
+    let letters: Vec<char> = formula.chars().collect();
+    if letters.len() != 2 || !letters.iter().all(|ch| ch.is_ascii() && ch.is_uppercase()) {
+        return None;
+    }
+    let mut lookup = |name: String| {
+        let len = name.len();
+        database.get_single_data(&PerElem { name, coef: 1, pos: 0, len }).ok()
+    };
+    let first = lookup(letters[0].to_string());
+    let second = lookup(letters[1].to_string());
+    let single = lookup(capitalize(formula));
+    match (first, second, single) {
+        (Some(first), Some(second), Some(single)) => {
+            Some(format!("`{}` is read as {} and {}, did you mean {} (`{}`)?", formula,
+                         first.long_name.to_ascii_lowercase(),
+                         second.long_name.to_ascii_lowercase(),
+                         single.long_name.to_ascii_lowercase(), single.short_name))
+        },
+        _ => None,
+    }
+}
+
+/// Finds the symbols which might have been meant by the word at the given position
+///
+/// Returns the word along with the suggestions, or None if there are no suggestions.
+fn suggest_at(input: &str, pos: usize, elems: &[ElemData]) -> Option<(String, Vec<String>)> {
+    if pos >= input.len() || !input.is_char_boundary(pos) {
+        return None;
+    }
+    // the word is read like a symbol, i.e. a letter followed by any number of lowercase letters
+    let rest = &input[pos..];
+    let len = rest.char_indices()
+                  .take_while(|&(i, ch)| ch.is_ascii() && ch.is_alphabetic() &&
+                                         (i == 0 || ch.is_lowercase()))
+                  .count();
+    if len == 0 {
+        return None;
+    }
+    let word = &rest[..len];
+    // for CL the parser stops at L, but it is the whole of CL that was mistyped
+    let before = &input[..pos];
+    let pair = match before.chars().last() {
+        Some(ch) if ch.is_ascii() && ch.is_uppercase() && len == 1 => {
+            Some(&input[pos - 1..pos + len])
+        },
+        _ => None,
+    };
+
+    let candidates = pair.into_iter().chain(Some(word));
+    for candidate in candidates.filter(|&c| !is_valid(c, elems)) {
+        let suggestions = suggest(candidate, elems);
+        if !suggestions.is_empty() {
+            return Some((candidate.to_string(), suggestions));
+        }
+    }
+    None
+}
+
+/// Finds the symbols which might have been meant by a word which is not a valid formula
+fn suggest(word: &str, elems: &[ElemData]) -> Vec<String> {
+    let is_symbol = |name: &str| elems.iter().any(|e| e.short_name.as_str() == name);
+    let lowercase = word.chars().all(|ch| ch.is_lowercase());
+    // a single lowercase letter is usually not a symbol at all, e.g. the electron in `e-`
+    if lowercase && word.chars().count() == 1 {
+        return Vec::new();
+    }
+
+    // a word in the wrong case is the most likely mistake, so typos are only looked for if the
+    // case can not be fixed
+    let mut out = Vec::new();
+    let capitalized = capitalize(word);
+    if capitalized.as_str() != word && is_symbol(capitalized.as_str()) {
+        out.push(capitalized);
+    }
+    // only a word typed in all lowercase can be a formula of single letter symbols, e.g. `co`
+    let upper = word.to_ascii_uppercase();
+    if lowercase && upper.chars().all(|ch| is_symbol(ch.to_string().as_str())) {
+        out.push(upper);
+    }
+    if !out.is_empty() {
+        return out;
+    }
+
+    // the full name of an element might have been written instead of its symbol, in which case
+    // only that element is suggested
+    let lower = word.to_ascii_lowercase();
+    if lower.len() > 3 {
+        let mut named: Vec<(usize, &ElemData)> = elems.iter().map(|elem| {
+            (distance(lower.as_str(), elem.long_name.to_ascii_lowercase().as_str()), elem)
+        }).filter(|&(dist, _)| dist <= 2).collect();
+        named.sort_by_key(|a| a.0);
+        if let Some(&(0, elem)) = named.first() {
+            return vec!(elem.short_name.clone());
+        }
+        if !named.is_empty() {
+            return named.into_iter().take(MAX_SUGGESTIONS)
+                        .map(|(_, elem)| elem.short_name.clone()).collect();
+        }
+    }
+
+    // otherwise the closest symbols are suggested, preferring those with the same first letter
+    let mut close: Vec<(usize, bool, &ElemData)> = elems.iter().map(|elem| {
+        let same_start = elem.short_name.chars().next() == word.chars().next();
+        (distance(word, elem.short_name.as_str()), !same_start, elem)
+    }).filter(|&(dist, _, _)| dist <= 1).collect();
+    close.sort_by_key(|a| (a.0, a.1));
+    close.into_iter().take(MAX_SUGGESTIONS).map(|(_, _, elem)| elem.short_name.clone()).collect()
+}
+
+/// Returns true if the word is made only of symbols from the database, e.g. `CO` or `NaCl`
+fn is_valid(word: &str, elems: &[ElemData]) -> bool {
+    let mut symbols = Vec::new();
+    for ch in word.chars() {
+        if ch.is_uppercase() {
+            symbols.push(ch.to_string());
+        } else if let Some(symbol) = symbols.last_mut() {
+            symbol.push(ch);
+        } else {
+            return false;
+        }
+    }
+    symbols.iter().all(|symbol| elems.iter().any(|e| e.short_name == *symbol))
+}
+
+/// Writes the first letter of a word in uppercase, and the rest in lowercase
+fn capitalize(word: &str) -> String {
+    word.chars().enumerate().map(|(i, ch)| {
+        if i == 0 { ch.to_ascii_uppercase() } else { ch.to_ascii_lowercase() }
+    }).collect()
+}
+
+/// Lists the suggestions as e.g. `La`, `Li` or `Lr`
+fn alternatives(suggestions: &[String]) -> String {
+    let quoted: Vec<String> = suggestions.iter().map(|s| format!("`{}`", s)).collect();
+    match quoted.len() {
+        0 | 1 => quoted.join(""),
+        n => format!("{} or {}", quoted[..n - 1].join(", "), quoted[n - 1]),
+    }
+}
+
+/// Finds the Levenshtein distance between two words, i.e. the number of characters which must be
+/// inserted, deleted or replaced to turn one into the other
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ch_a) in a.chars().enumerate() {
+        let mut crnt = vec!(i + 1);
+        for (j, &ch_b) in b.iter().enumerate() {
+            let replace = prev[j] + if ch_a == ch_b { 0 } else { 1 };
+            crnt.push(min(replace, min(prev[j + 1], crnt[j]) + 1));
+        }
+        prev = crnt;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn elems() -> Vec<ElemData> {
+        [("C", "Carbon"), ("Cl", "Chlorine"), ("Co", "Cobalt"), ("Na", "Sodium"), ("O", "Oxygen"),
+         ("Xe", "Xenon")].iter().map(|&(symbol, name)| {
+            ElemData {
+                short_name: symbol.to_string(),
+                long_name: name.to_string(),
+                mass: 1.0,
+                atomic_num: 1,
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn case() {
+        assert_eq!(suggest("CL", &elems()), vec!("Cl".to_string()));
+        assert_eq!(suggest("co", &elems()), vec!("Co".to_string(), "CO".to_string()));
+        // only an all lowercase word is read as single letter symbols
+        assert!(!suggest("Oo", &elems()).contains(&"OO".to_string()));
+        assert_eq!(suggest("e", &elems()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn typo() {
+        assert_eq!(suggest("Xa", &elems()), vec!("Xe".to_string(), "Na".to_string()));
+        assert_eq!(suggest("Sodum", &elems()), vec!("Na".to_string()));
+    }
+
+    #[test]
+    fn names() {
+        let mut database = ElemDatabase::embedded();
+        let elems = database.all_data().unwrap();
+        assert_eq!(suggest("carbon", &elems), vec!("C".to_string()));
+        assert_eq!(suggest("Sodium", &elems), vec!("Na".to_string()));
+        assert!(!suggest("Yy", &elems).contains(&"YY".to_string()));
+        assert_eq!(suggest("e", &elems), Vec::<String>::new());
+    }
+
+    #[test]
+    fn valid() {
+        assert!(is_valid("CO", &elems()));
+        assert!(is_valid("NaCl", &elems()));
+        assert!(!is_valid("CL", &elems()));
+        assert!(!is_valid("co", &elems()));
+    }
+
+    #[test]
+    fn mistyped_pair() {
+        let mut database = ElemDatabase::embedded();
+        let molecule = crate::parse_molecule("CL").unwrap();
+        let err = database.get_data(&molecule).unwrap_err();
+        assert_eq!(err.desc, "Could not find element: \"L\"");
+        assert_eq!(add_help(err, "CL", &mut database).help,
+                   Some("did you mean `Cl`?".to_string()));
+    }
+
+    #[test]
+    fn edit_distance() {
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "Co"), 2);
+        assert_eq!(distance("Co", "Co"), 0);
+    }
+}
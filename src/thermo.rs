@@ -0,0 +1,315 @@
+//! Functions for thermochemical calculations on balanced reactions.
+//!
+//! The standard reaction enthalpy, entropy and Gibbs energy are found from the standard formation
+//! quantities of each species, which are read from a database of the following format:
+//! `<formula>;<state>;<ΔHf° in kJ/mol>;<S° in J/(mol·K)>;<ΔGf° in kJ/mol>`
+//! A formula may appear once for each state it can be in. When a reaction does not give the state
+//! of a species, the first entry for that formula in the database is used, so the standard state
+//! should always be listed first.
+
+use std::fs;
+use std::path::Path;
+use crate::arith;
+use crate::elem;
+use crate::elem::{Molecule, State, Species, Reaction};
+use crate::parser::Parser;
+use crate::json::Json;
+use crate::error::{CTResult, CTError};
+use crate::error::CTErrorKind::{InputError, DatabaseError};
+
+/// The molar gas constant in J/(mol·K)
+const GAS_CONSTANT: f64 = 8.314462618;
+
+/// The temperature at which the standard formation quantities are tabulated
+pub const STANDARD_TEMP: f64 = 298.15;
+
+#[derive(Debug, PartialEq)]
+pub struct ThermoData {
+    pub formula: String,
+    pub molecule: Molecule,
+    pub state: State,
+    pub enthalpy: f64,
+    pub entropy: f64,
+    pub gibbs: f64,
+}
+
+/// The standard quantities of a reaction, in kJ/mol, J/(mol·K) and kJ/mol respectively
+#[derive(Debug, PartialEq)]
+pub struct ReactionThermo {
+    pub enthalpy: f64,
+    pub entropy: f64,
+    pub gibbs: f64,
+}
+
+/// The header for the table made by `to_rows`
+pub static TABLE_HEADER: [&str; 5] =
+    ["temperature", "enthalpy", "entropy", "gibbs", "equilibrium_constant"];
+
+pub struct ThermoDatabase {
+    entries: Vec<ThermoData>,
+}
+
+impl ThermoDatabase {
+    /// Try to read the database in the file at the given path
+    pub fn open(path: &Path) -> CTResult<ThermoDatabase> {
+        match fs::read_to_string(path) {
+            Ok(contents) => ThermoDatabase::from_csv(contents.as_str()),
+            Err(e) => {
+                let desc = format!("Could not open thermodynamic database file. Expected at: {:?}",
+                                   path.to_str().unwrap_or("same directory as the program"));
+                Err(CTError::new(DatabaseError, desc).with_source(e))
+            },
+        }
+    }
+
+    /// Try to make the database from the contents of a database file
+    pub fn from_csv(contents: &str) -> CTResult<ThermoDatabase> {
+        let mut entries = Vec::new();
+        for line in contents.lines().filter(|l| !l.trim().is_empty()) {
+            entries.push(decode_line(line)?);
+        }
+        Ok(ThermoDatabase { entries })
+    }
+
+    /// Try to find the data for a molecule in the given state
+    ///
+    /// If no state is given, the first entry matching the molecule is returned.
+    pub fn get_single_data(&self, molecule: &Molecule, state: Option<State>)
+                           -> Option<&ThermoData> {
+        self.entries.iter().find(|data| {
+            arith::same_formula(&data.molecule, molecule) && state.is_none_or(|s| s == data.state)
+        })
+    }
+
+    /// Try to get the data for all the species in a reaction
+    ///
+    /// The data is in the same order as `Reaction::species`. This function errors with a list of
+    /// every species which could not be found.
+    pub fn get_reaction_data(&self, reaction: &Reaction) -> CTResult<Vec<&ThermoData>> {
+        let mut out = Vec::new();
+        let mut missing = Vec::new();
+        for species in reaction.species() {
+            match self.get_single_data(&species.molecule, species.state) {
+                Some(data) => out.push(data),
+                None => missing.push(format!("{}", species)),
+            }
+        }
+        if missing.is_empty() {
+            Ok(out)
+        } else {
+            Err(CTError::new(InputError,
+                             format!("Missing thermodynamic data for: {}", missing.join(", "))))
+        }
+    }
+}
+
+/// Sums up the standard formation quantities of the species in a balanced reaction
+///
+/// The species data must be in the same order as `Reaction::species`, which is how
+/// `get_reaction_data` returns it.
+pub fn reaction_thermo(reaction: &Reaction, species: &[&ThermoData]) -> ReactionThermo {
+    let mut out = ReactionThermo { enthalpy: 0.0, entropy: 0.0, gibbs: 0.0 };
+    let lhs_len = reaction.reactants.len();
+    for (i, (data, coef)) in species.iter().zip(reaction.coefs()).enumerate() {
+        // reactants are consumed, so they count negatively
+        let sign = if i < lhs_len { -1.0 } else { 1.0 };
+        out.enthalpy += sign * coef as f64 * data.enthalpy;
+        out.entropy += sign * coef as f64 * data.entropy;
+        out.gibbs += sign * coef as f64 * data.gibbs;
+    }
+    out
+}
+
+/// Finds the Gibbs energy of reaction in kJ/mol at the given temperature
+///
+/// At the standard temperature the tabulated value is used. Otherwise it is estimated from
+/// ΔG = ΔH - TΔS, assuming that ΔH and ΔS do not depend on the temperature.
+pub fn gibbs_at(thermo: &ReactionThermo, temp: f64) -> f64 {
+    if temp == STANDARD_TEMP {
+        thermo.gibbs
+    } else {
+        thermo.enthalpy - temp * thermo.entropy / 1000.0
+    }
+}
+
+/// Finds the equilibrium constant from the Gibbs energy of reaction (in kJ/mol)
+pub fn equilibrium_constant(gibbs: f64, temp: f64) -> f64 {
+    (-gibbs * 1000.0 / (GAS_CONSTANT * temp)).exp()
+}
+
+/// Pretty prints the balanced reaction and its thermodynamic quantities
+///
+/// The quantities are always printed at the standard temperature, and also at `temp` if given.
+pub fn pretty_print(reaction: &Reaction, thermo: &ReactionThermo, temp: Option<f64>) {
+    println!("{}", format_reaction(reaction));
+    println!("ΔH° = {:.2} kJ/mol", thermo.enthalpy);
+    println!("ΔS° = {:.2} J/(mol·K)", thermo.entropy);
+    for &t in temperatures(temp).iter() {
+        let gibbs = gibbs_at(thermo, t);
+        println!("ΔG°({:.2} K) = {:.2} kJ/mol    K = {:.3e}",
+                 t, gibbs, equilibrium_constant(gibbs, t));
+    }
+}
+
+/// Makes the rows of a table with the thermodynamic quantities, one row for each temperature
+pub fn to_rows(thermo: &ReactionThermo, temp: Option<f64>) -> Vec<Vec<String>> {
+    temperatures(temp).iter().map(|&t| {
+        let gibbs = gibbs_at(thermo, t);
+        vec!(format!("{}", t),
+             format!("{}", thermo.enthalpy),
+             format!("{}", thermo.entropy),
+             format!("{}", gibbs),
+             format!("{}", equilibrium_constant(gibbs, t)))
+    }).collect()
+}
+
+/// Makes a JSON object with the same data as printed by `pretty_print`
+///
+/// The Gibbs energy and equilibrium constant are given in the array `gibbs`, with an entry for
+/// each temperature.
+pub fn to_json(reaction: &Reaction, thermo: &ReactionThermo, temp: Option<f64>) -> Json {
+    let gibbs = temperatures(temp).iter().map(|&t| {
+        let gibbs = gibbs_at(thermo, t);
+        Json::object(vec!(
+            ("temperature", Json::Number(t)),
+            ("gibbs", Json::Number(gibbs)),
+            ("equilibrium_constant", Json::Number(equilibrium_constant(gibbs, t))),
+        ))
+    }).collect();
+    Json::object(vec!(
+        ("reaction", Json::String(format_reaction(reaction))),
+        ("enthalpy", Json::Number(thermo.enthalpy)),
+        ("entropy", Json::Number(thermo.entropy)),
+        ("gibbs", Json::Array(gibbs)),
+    ))
+}
+
+/// Writes the reaction with the coefficient and state of every species, e.g. `1 H2O(l)`
+fn format_reaction(reaction: &Reaction) -> String {
+    let side = |species: &[Species]| {
+        let names: Vec<String> = species.iter().map(|s| format!("{} {}", s.coef, s)).collect();
+        names.join(" + ")
+    };
+    format!("{} {} {}", side(&reaction.reactants), reaction.arrow_text(),
+            side(&reaction.products))
+}
+
+fn temperatures(temp: Option<f64>) -> Vec<f64> {
+    let mut temps = vec!(STANDARD_TEMP);
+    temps.extend(temp);
+    temps
+}
+
+fn decode_line(line: &str) -> CTResult<ThermoData> {
+    let data: Vec<&str> = line.trim().split(';').collect();
+    if data.len() < 5 {
+        return Err(CTError::new(DatabaseError, "Missing field in thermodynamic database"));
+    }
+    let mut parser = Parser::new(data[0]);
+    let molecule = parser.parse_molecule();
+    let state = match data[1] {
+        "s" => Some(State::Solid),
+        "l" => Some(State::Liquid),
+        "g" => Some(State::Gas),
+        "aq" => Some(State::Aqueous),
+        _ => None,
+    };
+    let enthalpy = data[2].parse::<f64>();
+    let entropy = data[3].parse::<f64>();
+    let gibbs = data[4].parse::<f64>();
+    match (molecule, state, enthalpy, entropy, gibbs) {
+        (Ok(m), Some(s), Ok(h), Ok(e), Ok(g)) => Ok(ThermoData {
+            formula: data[0].to_string(),
+            molecule: elem::group_elems(m),
+            state: s,
+            enthalpy: h,
+            entropy: e,
+            gibbs: g,
+        }),
+        (molecule, _, enthalpy, entropy, gibbs) => {
+            let desc = format!("Field in thermodynamic database corrupted: {:?}", line);
+            let err = CTError::new(DatabaseError, desc);
+            // an unknown state has no error of its own to pass on
+            Err(match (molecule, enthalpy.and(entropy).and(gibbs)) {
+                (Err(e), _) => err.with_source(e),
+                (Ok(_), Err(e)) => err.with_source(e),
+                (Ok(_), Ok(_)) => err,
+            })
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::elem::State;
+    use crate::parser::Parser;
+
+    static DUMMY_DB: &str = "\
+        H2O;l;-285.83;69.91;-237.13\n\
+        H2O;g;-241.82;188.83;-228.57\n\
+        CO2;g;-393.51;213.74;-394.36\n\
+        CH4;g;-74.81;186.26;-50.72\n\
+        O2;g;0;205.14;0\n";
+
+    fn assert_close(result: f64, expected: f64) {
+        assert!((result - expected).abs() < 1e-6, "{} != {}", result, expected);
+    }
+
+    #[test]
+    fn default_state() {
+        let db = ThermoDatabase::from_csv(DUMMY_DB).unwrap();
+        let molecule = Parser::new("H2O").parse_molecule().unwrap();
+        assert_eq!(db.get_single_data(&molecule, None).unwrap().state, State::Liquid);
+    }
+
+    #[test]
+    fn explicit_state() {
+        let db = ThermoDatabase::from_csv(DUMMY_DB).unwrap();
+        let molecule = Parser::new("H2O").parse_molecule().unwrap();
+        let data = db.get_single_data(&molecule, Some(State::Gas)).unwrap();
+        assert_close(data.enthalpy, -241.82);
+    }
+
+    #[test]
+    fn combustion() {
+        let db = ThermoDatabase::from_csv(DUMMY_DB).unwrap();
+        let mut parser = Parser::new("CH4(g) + 2 O2(g) -> CO2(g) + 2 H2O(l)");
+        let reaction = parser.parse_reaction().unwrap();
+        let species = db.get_reaction_data(&reaction).unwrap();
+        let result = reaction_thermo(&reaction, &species);
+        assert_close(result.enthalpy, -890.36);
+        assert_close(result.entropy, -242.98);
+        assert_close(result.gibbs, -817.90);
+    }
+
+    #[test]
+    fn missing_species() {
+        let db = ThermoDatabase::from_csv(DUMMY_DB).unwrap();
+        let mut parser = Parser::new("CH4(l) + O2 -> CO2 + H2O");
+        let reaction = parser.parse_reaction().unwrap();
+        let result = db.get_reaction_data(&reaction);
+        assert!(result.is_err());
+        assert_eq!(result.err().unwrap().desc, "Missing thermodynamic data for: CH4(l)");
+    }
+
+    #[test]
+    fn gibbs_away_from_standard_temp() {
+        let thermo = ReactionThermo { enthalpy: -100.0, entropy: -100.0, gibbs: -70.0 };
+        assert_close(gibbs_at(&thermo, STANDARD_TEMP), -70.0);
+        assert_close(gibbs_at(&thermo, 1000.0), 0.0);
+    }
+
+    #[test]
+    fn equilibrium() {
+        assert_close(equilibrium_constant(0.0, 500.0), 1.0);
+        assert!(equilibrium_constant(-10.0, STANDARD_TEMP) > 1.0);
+    }
+
+    #[test]
+    fn corrupted_line() {
+        assert!(ThermoDatabase::from_csv("H2O;l;x;69.91;-237.13\n").is_err());
+        assert!(ThermoDatabase::from_csv("H2O;plasma;0;0;0\n").is_err());
+        assert!(ThermoDatabase::from_csv("H2O;l;0\n").is_err());
+    }
+}
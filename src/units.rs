@@ -0,0 +1,275 @@
+//! Functions for parsing physical quantities given on the commandline, e.g. `250mL` or `0.1M`.
+//!
+//! Every quantity is converted to a fixed base unit for its kind (litres for volumes, grams for
+//! masses, etc.), so the calculations never have to care about which unit the user typed.
+
+use crate::error::{CTResult, CTError};
+use crate::error::CTErrorKind::{InputError, UsageError};
+
+/// The name of a unit, and the factor which converts a value in that unit to the base unit
+pub type Unit = (&'static str, f64);
+
+/// Volumes, in litres
+pub static VOLUME: &[Unit] = &[
+    ("L", 1.0), ("l", 1.0), ("dm3", 1.0), ("dm³", 1.0),
+    ("mL", 1e-3), ("ml", 1e-3), ("cm3", 1e-3), ("cm³", 1e-3),
+    ("uL", 1e-6), ("µL", 1e-6),
+    ("m3", 1e3), ("m³", 1e3),
+];
+
+/// Masses, in grams
+pub static MASS: &[Unit] = &[
+    ("g", 1.0), ("kg", 1e3), ("mg", 1e-3), ("ug", 1e-6), ("µg", 1e-6),
+];
+
+/// Amounts of substance, in moles
+pub static AMOUNT: &[Unit] = &[
+    ("mol", 1.0), ("mmol", 1e-3), ("umol", 1e-6), ("µmol", 1e-6),
+];
+
+/// Molar concentrations, in mol/L
+pub static MOLARITY: &[Unit] = &[
+    ("M", 1.0), ("mol/L", 1.0), ("mM", 1e-3), ("mmol/L", 1e-3), ("uM", 1e-6), ("µM", 1e-6),
+];
+
+/// Molal concentrations, in mol/kg
+pub static MOLALITY: &[Unit] = &[
+    ("m", 1.0), ("mol/kg", 1.0), ("mmol/kg", 1e-3),
+];
+
+/// Densities, in g/mL
+pub static DENSITY: &[Unit] = &[
+    ("g/mL", 1.0), ("g/ml", 1.0), ("g/cm3", 1.0), ("g/cm³", 1.0), ("kg/L", 1.0),
+    ("g/L", 1e-3), ("kg/m3", 1e-3), ("kg/m³", 1e-3),
+];
+
+/// Pressures, in pascal
+pub static PRESSURE: &[Unit] = &[
+    ("Pa", 1.0), ("kPa", 1e3), ("MPa", 1e6), ("hPa", 1e2),
+    ("bar", 1e5), ("mbar", 1e2), ("atm", 101325.0),
+    ("mmHg", 133.322387415), ("torr", 101325.0 / 760.0), ("Torr", 101325.0 / 760.0),
+];
+
+/// Temperature scales, and the offset which converts a temperature on that scale to kelvin
+static TEMPERATURE: &[Unit] = &[
+    ("°C", 273.15), ("degC", 273.15), ("C", 273.15), ("K", 0.0),
+];
+
+/// Fractions, as a number between 0 and 1
+pub static FRACTION: &[Unit] = &[
+    ("%", 1e-2),
+];
+
+/// Parses a quantity such as `250mL` and converts it to the base unit of the given unit table
+///
+/// Whitespace is allowed between the number and the unit. If no unit is given, the number is
+/// assumed to already be in the base unit. Infinite and NaN values are rejected.
+pub fn parse_quantity(input: &str, units: &[Unit]) -> CTResult<f64> {
+    let input = input.trim();
+    // we look for the longest prefix which is a valid number, since units like m3 contain digits
+    let mut bounds: Vec<usize> = input.char_indices().map(|(i, _)| i).skip(1).collect();
+    bounds.push(input.len());
+    for &i in bounds.iter().rev() {
+        if let Ok(num) = input[..i].parse::<f64>() {
+            let unit = input[i..].trim();
+            let factor = if unit.is_empty() {
+                1.0
+            } else {
+                match units.iter().find(|&&(name, _)| name == unit) {
+                    Some(&(_, factor)) => factor,
+                    None => {
+                        let desc = format!("Unknown unit {:?} in {:?}. Expected one of: {}",
+                                           unit, input, unit_names(units));
+                        return Err(CTError::new(InputError, desc));
+                    },
+                }
+            };
+            // `str::parse` accepts `NaN` and `inf`, which no calculation can make sense of
+            let value = num * factor;
+            return if value.is_finite() {
+                Ok(value)
+            } else {
+                let desc = format!("Expected a finite quantity, found {:?}", input);
+                Err(CTError::new(InputError, desc))
+            };
+        }
+    }
+    Err(CTError::new(InputError, format!("Could not parse quantity {:?}", input)))
+}
+
+/// Parses a temperature such as `25°C` and converts it to kelvin
+///
+/// Since the temperature scales do not share a zero point, this can not be done with a simple
+/// unit table like the other quantities. If no unit is given, the temperature is assumed to be
+/// in kelvin.
+pub fn parse_temperature(input: &str) -> CTResult<f64> {
+    let input = input.trim();
+    for &(suffix, offset) in TEMPERATURE.iter() {
+        if let Some(num) = input.strip_suffix(suffix) {
+            let num = parse_quantity(num, &[])?;
+            return Ok(num + offset);
+        }
+    }
+    parse_quantity(input, &[])
+}
+
+/// The relative difference allowed between a given quantity and the same quantity derived from
+/// the others, since the given quantities are usually rounded
+pub const TOLERANCE: f64 = 1e-2;
+
+/// Errors if a quantity given by the user is zero, negative, infinite or NaN
+///
+/// Quantities which are not known are always accepted.
+pub fn check_positive(name: &str, value: Option<f64>) -> CTResult<()> {
+    match value {
+        Some(value) if !(value > 0.0 && value.is_finite()) => {
+            let desc = format!("The {} must be positive, found: {}", name, value);
+            Err(CTError::new(InputError, desc))
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Errors if a temperature in kelvin is at or below absolute zero, infinite or NaN
+pub fn check_temperature(kelvin: f64) -> CTResult<()> {
+    if !kelvin.is_finite() {
+        Err(CTError::new(InputError, format!("Expected a finite temperature, found: {}", kelvin)))
+    } else if kelvin <= 0.0 {
+        let desc = format!("The temperature must be above absolute zero, found: {:.2} K", kelvin);
+        Err(CTError::new(InputError, desc))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns true if the two quantities are equal within `TOLERANCE`
+pub fn roughly_equal(a: f64, b: f64) -> bool {
+    (a - b).abs() <= TOLERANCE * a.abs().max(b.abs())
+}
+
+/// Splits commandline arguments of the form `key=value` into (key, value) pairs
+pub fn parse_assignments(args: &[String]) -> CTResult<Vec<(String, String)>> {
+    let mut out = Vec::new();
+    for arg in args.iter() {
+        let parts: Vec<&str> = arg.splitn(2, '=').collect();
+        if parts.len() != 2 || parts[0].trim().is_empty() {
+            let desc = format!("Expected an argument of the form key=value, found: {:?}", arg);
+            return Err(CTError::new(UsageError, desc));
+        }
+        out.push((parts[0].trim().to_string(), parts[1].trim().to_string()));
+    }
+    Ok(out)
+}
+
+fn unit_names(units: &[Unit]) -> String {
+    let names: Vec<&str> = units.iter().map(|&(name, _)| name).collect();
+    names.join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn not_finite() {
+        assert!(parse_quantity("NaN", MOLARITY).is_err());
+        assert!(parse_quantity("inf M", MOLARITY).is_err());
+        assert_eq!(parse_quantity("-infinity", MOLARITY).unwrap_err().desc,
+                   "Expected a finite quantity, found \"-infinity\"");
+        // finite numbers can still overflow when converted to the base unit
+        assert!(parse_quantity("1e308 m3", VOLUME).is_err());
+    }
+
+    #[test]
+    fn roughly() {
+        assert!(roughly_equal(5.844, 5.84));
+        assert!(!roughly_equal(5.844, 10.0));
+        assert!(roughly_equal(0.0, 0.0));
+    }
+
+    #[test]
+    fn positive() {
+        assert_eq!(check_positive("volume", Some(0.5)), Ok(()));
+        assert_eq!(check_positive("volume", None), Ok(()));
+        assert_eq!(check_positive("volume", Some(0.0)).unwrap_err().desc,
+                   "The volume must be positive, found: 0");
+        assert!(check_positive("volume", Some(-1.0)).is_err());
+        assert!(check_positive("volume", Some(f64::NAN)).is_err());
+        assert!(check_positive("volume", Some(f64::INFINITY)).is_err());
+    }
+
+    #[test]
+    fn absolute_zero() {
+        assert_eq!(check_temperature(0.01), Ok(()));
+        assert_eq!(check_temperature(0.0).unwrap_err().desc,
+                   "The temperature must be above absolute zero, found: 0.00 K");
+        assert!(check_temperature(parse_temperature("-300°C").unwrap()).is_err());
+        assert_eq!(check_temperature(f64::NAN).unwrap_err().desc,
+                   "Expected a finite temperature, found: NaN");
+        assert!(check_temperature(f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn plain_number() {
+        assert_eq!(parse_quantity("2.5", VOLUME), Ok(2.5));
+    }
+
+    #[test]
+    fn with_unit() {
+        assert_eq!(parse_quantity("250mL", VOLUME), Ok(0.25));
+        assert_eq!(parse_quantity("0.1 M", MOLARITY), Ok(0.1));
+    }
+
+    #[test]
+    fn unit_with_digit() {
+        assert_eq!(parse_quantity("2m3", VOLUME), Ok(2000.0));
+    }
+
+    #[test]
+    fn exponent() {
+        assert_eq!(parse_quantity("1e3mL", VOLUME), Ok(1.0));
+    }
+
+    #[test]
+    fn unknown_unit() {
+        assert!(parse_quantity("12 parsecs", VOLUME).is_err());
+    }
+
+    #[test]
+    fn not_a_number() {
+        assert!(parse_quantity("mL", VOLUME).is_err());
+    }
+
+    #[test]
+    fn pressure() {
+        assert_eq!(parse_quantity("1atm", PRESSURE), Ok(101325.0));
+        assert_eq!(parse_quantity("1 bar", PRESSURE), Ok(100000.0));
+    }
+
+    #[test]
+    fn temperature() {
+        assert_eq!(parse_temperature("300K"), Ok(300.0));
+        assert_eq!(parse_temperature("25°C"), Ok(298.15));
+        assert_eq!(parse_temperature("0 C"), Ok(273.15));
+        assert_eq!(parse_temperature("273.15"), Ok(273.15));
+    }
+
+    #[test]
+    fn bad_temperature() {
+        assert!(parse_temperature("hot").is_err());
+    }
+
+    #[test]
+    fn assignments() {
+        let args = vec!("c=0.1M".to_string(), "V = 250mL".to_string());
+        let expected = vec!(("c".to_string(), "0.1M".to_string()),
+                            ("V".to_string(), "250mL".to_string()));
+        assert_eq!(parse_assignments(&args), Ok(expected));
+    }
+
+    #[test]
+    fn bad_assignment() {
+        let args = vec!("250mL".to_string());
+        assert!(parse_assignments(&args).is_err());
+    }
+}
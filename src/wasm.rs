@@ -0,0 +1,91 @@
+//! The JavaScript API of the library, built with the `wasm` feature.
+//!
+//! Each function takes a string and returns a string of JSON, which is the same JSON as written
+//! by the program with `--format json`. An error is returned as the JSON object for the error, with
+//! a single field `error`. The elements are looked up in the table built into the library, so no
+//! filesystem is needed.
+
+use wasm_bindgen::prelude::*;
+use crate::database::ElemDatabase;
+use crate::error::CTResult;
+use crate::json::Json;
+use crate::{elem, mass, parser, suggest};
+
+/// Writes the result as JSON, or the error if there is one
+fn to_json_string(result: CTResult<Json>) -> String {
+    match result {
+        Ok(json) => json.to_string(),
+        Err(e) => e.to_json().to_string(),
+    }
+}
+
+/// Finds the molar mass of a formula, along with the data for each of its elements
+#[wasm_bindgen(js_name = molarMass)]
+pub fn molar_mass(formula: &str) -> String {
+    let mut database = ElemDatabase::embedded();
+    to_json_string(parser::parse_single_molecule(formula).and_then(|parsed| {
+        let molecule = elem::strip_charge(parsed.clone());
+        let data = database.get_data(&molecule)
+                           .map_err(|e| suggest::add_help(e, formula, &mut database))?;
+        Ok(mass::to_json(&data, &parsed))
+    }))
+}
+
+/// Balances a reaction, giving the balanced reaction and the coefficient of each species
+#[wasm_bindgen]
+pub fn balance(reaction: &str) -> String {
+    to_json_string(crate::balance(reaction).map(|balanced| crate::balance::to_json(&balanced)))
+}
+
+/// Looks up an element by its symbol, giving its name, molar mass and atomic number
+#[wasm_bindgen]
+pub fn element(symbol: &str) -> String {
+    let mut database = ElemDatabase::embedded();
+    to_json_string(crate::element(&mut database, symbol).map(|data| {
+        Json::object(vec!(
+            ("symbol", Json::string(&data.short_name)),
+            ("name", Json::string(&data.long_name)),
+            ("molar_mass", Json::Number(data.mass)),
+            ("atomic_number", Json::Number(data.atomic_num as f64)),
+        ))
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test as test;
+
+    #[test]
+    fn mass() {
+        assert_eq!(molar_mass("H2O"),
+                   "{\"formula\":\"H2O\",\"elements\":[\
+                    {\"symbol\":\"H\",\"count\":2,\"molar_mass\":1.00794,\"name\":\"Hydrogen\",\
+                    \"atomic_number\":1,\"mass\":2.01588},\
+                    {\"symbol\":\"O\",\"count\":1,\"molar_mass\":15.9994,\"name\":\"Oxygen\",\
+                    \"atomic_number\":8,\"mass\":15.9994}],\"total\":18.01528}");
+    }
+
+    #[test]
+    fn balanced() {
+        assert_eq!(balance("H2 + O2 -> H2O"),
+                   "{\"reaction\":\"2 H2 + 1 O2 -> 2 H2O\",\"reactants\":[\
+                    {\"formula\":\"H2\",\"coefficient\":2},{\"formula\":\"O2\",\"coefficient\":1}\
+                    ],\"products\":[{\"formula\":\"H2O\",\"coefficient\":2}]}");
+    }
+
+    #[test]
+    fn lookup() {
+        assert_eq!(element("Fe"),
+                   "{\"symbol\":\"Fe\",\"name\":\"Iron\",\"molar_mass\":55.845,\
+                    \"atomic_number\":26}");
+    }
+
+    #[test]
+    fn error() {
+        assert_eq!(molar_mass("Xq"),
+                   "{\"error\":{\"kind\":\"InputError\",\"desc\":\"Could not find element: \
+                    \\\"Xq\\\"\",\"pos\":[0,2],\"labels\":[],\"help\":\"did you mean `Xe`?\"}}");
+    }
+}
@@ -0,0 +1,131 @@
+/*
+ * Exercises the C interface the way a C or C++ program would use it. It is compiled and run
+ * against the cdylib by tests/ffi.rs, with the path to the element database as its argument.
+ */
+
+#include <math.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "chemtool.h"
+
+static int failures = 0;
+
+#define CHECK(cond) do { \
+    if (!(cond)) { \
+        fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+        failures++; \
+    } \
+} while (0)
+
+static void test_parse(void) {
+    ct_molecule *molecule = NULL;
+    ct_error *err = NULL;
+    CHECK(ct_parse_molecule("SO4^2-", &molecule, &err) == CT_OK);
+    CHECK(err == NULL);
+    CHECK(ct_molecule_len(molecule) == 3);
+    /* the elements are sorted by symbol, which puts the charge first */
+    CHECK(strcmp(ct_molecule_symbol(molecule, 0), "-") == 0);
+    CHECK(ct_molecule_count(molecule, 0) == 2);
+    CHECK(strcmp(ct_molecule_symbol(molecule, 1), "O") == 0);
+    CHECK(ct_molecule_count(molecule, 1) == 4);
+    CHECK(ct_molecule_symbol(molecule, 3) == NULL);
+    CHECK(ct_molecule_count(molecule, 3) == 0);
+    char *formula = ct_molecule_formula(molecule);
+    CHECK(strcmp(formula, "O4S^2-") == 0);
+    ct_string_free(formula);
+    ct_molecule_free(molecule);
+}
+
+static void test_parse_error(void) {
+    ct_molecule *molecule = NULL;
+    ct_error *err = NULL;
+    size_t pos = 0, len = 0;
+    CHECK(ct_parse_molecule("H2O)", &molecule, &err) == CT_INPUT_ERROR);
+    CHECK(molecule == NULL);
+    CHECK(ct_error_kind(err) == CT_INPUT_ERROR);
+    CHECK(strcmp(ct_error_message(err), "Missing opening parentheses") == 0);
+    CHECK(ct_error_span(err, &pos, &len) == 1);
+    CHECK(pos == 3 && len == 1);
+    char *message = ct_error_render(err, "H2O)", 0);
+    CHECK(strcmp(message, "Missing opening parentheses\n    H2O)\n       ^\n") == 0);
+    ct_string_free(message);
+    ct_error_free(err);
+
+    /* the error may be ignored, without leaking it */
+    CHECK(ct_parse_molecule(NULL, &molecule, NULL) == CT_USAGE_ERROR);
+
+    /* coefficients which do not fit are errors, not crashes */
+    err = NULL;
+    CHECK(ct_parse_molecule("(H2)4294967295", &molecule, &err) == CT_INPUT_ERROR);
+    CHECK(strcmp(ct_error_message(err), "The coefficient is too large") == 0);
+    ct_error_free(err);
+    err = NULL;
+    CHECK(ct_parse_molecule("H4294967295H", &molecule, &err) == CT_INPUT_ERROR);
+    ct_error_free(err);
+}
+
+static void test_molar_mass(const char *path) {
+    ct_database *database = NULL;
+    ct_error *err = NULL;
+    double mass = 0.0;
+    CHECK(ct_database_open(path, &database, &err) == CT_OK);
+    CHECK(ct_molar_mass(database, "H2O", &mass, &err) == CT_OK);
+    CHECK(fabs(mass - 18.015) < 0.01);
+    CHECK(ct_molar_mass(database, "Xx", &mass, &err) != CT_OK);
+    CHECK(err != NULL && strlen(ct_error_message(err)) > 0);
+    ct_error_free(err);
+    ct_database_free(database);
+
+    err = NULL;
+    CHECK(ct_database_open("no/such/file.csv", &database, &err) == CT_DATABASE_ERROR);
+    ct_error_free(err);
+
+    err = NULL;
+    CHECK(ct_molar_mass(NULL, "H2O", &mass, &err) == CT_USAGE_ERROR);
+    CHECK(strcmp(ct_error_message(err), "Missing database") == 0);
+    ct_error_free(err);
+}
+
+static void test_balance(void) {
+    uint32_t coefs[4] = {0};
+    size_t count = 0;
+    ct_error *err = NULL;
+    CHECK(ct_balance("C3H8 + O2 -> CO2 + H2O", coefs, 4, &count, &err) == CT_OK);
+    CHECK(count == 4);
+    CHECK(coefs[0] == 1 && coefs[1] == 5 && coefs[2] == 3 && coefs[3] == 4);
+
+    /* a buffer which is too small reports how large it must be */
+    uint32_t small[2] = {0};
+    CHECK(ct_balance("H2 + O2 -> H2O", small, 2, &count, &err) == CT_USAGE_ERROR);
+    CHECK(count == 3);
+    CHECK(small[0] == 0 && small[1] == 0);
+    ct_error_free(err);
+
+    err = NULL;
+    CHECK(ct_balance("H2 + O2", coefs, 4, &count, &err) == CT_INPUT_ERROR);
+    CHECK(ct_error_kind(err) == CT_INPUT_ERROR);
+    size_t labels = ct_error_label_count(err);
+    for (size_t i = 0; i < labels; i++) {
+        size_t pos, len;
+        CHECK(ct_error_label(err, i, &pos, &len) != NULL);
+    }
+    CHECK(ct_error_label(err, labels, NULL, NULL) == NULL);
+    ct_error_free(err);
+}
+
+int main(int argc, char **argv) {
+    if (argc != 2) {
+        fprintf(stderr, "usage: %s <elemdb.csv>\n", argv[0]);
+        return 2;
+    }
+    test_parse();
+    test_parse_error();
+    test_molar_mass(argv[1]);
+    test_balance();
+    if (failures > 0) {
+        fprintf(stderr, "%d checks failed\n", failures);
+        return 1;
+    }
+    return 0;
+}
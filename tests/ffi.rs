@@ -0,0 +1,35 @@
+//! Compiles the C harness in `tests/ffi.c` against the cdylib, and runs it
+
+// there is no C compiler to run from WebAssembly
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_harness() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // the test is run from target/<profile>/deps, and the cdylib is built in target/<profile>
+    let exe = env::current_exe().unwrap();
+    let lib_dir = exe.parent().unwrap().parent().unwrap().to_path_buf();
+    let harness = lib_dir.join("ffi_harness");
+
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(cc)
+        .arg(root.join("tests").join("ffi.c"))
+        .arg("-I").arg(root.join("include"))
+        .arg("-L").arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .args(["-lchemtool", "-lm", "-o"])
+        .arg(&harness)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "failed to compile the C harness");
+
+    let status = Command::new(&harness)
+        .arg(root.join("elemdb.csv"))
+        .status()
+        .expect("failed to run the C harness");
+    assert!(status.success(), "the C harness failed");
+}